@@ -1,12 +1,21 @@
+pub mod backup;
 pub mod config;
 pub mod copy;
 pub mod delete;
+pub mod dupes;
+pub mod files;
+pub mod hierarchy;
 pub mod imply;
 pub mod info;
 pub mod init;
 pub mod merge;
 pub mod rename;
+pub mod repair;
+pub mod saved_query;
+pub mod status;
 pub mod tags;
+pub mod untagged;
+pub mod vacuum;
 pub mod values;
 
 use error_chain::ensure;
@@ -0,0 +1,19 @@
+use std::path::Path;
+
+use crate::errors::*;
+use crate::storage::Storage;
+
+/// Copy the database at `db_path` into a fresh standalone `.tmsu` database at `dest`, using
+/// SQLite's online backup API so the copy is safe even while `db_path` is open for writes
+/// elsewhere. `on_progress` is called after each step with (pages remaining, total pages).
+pub fn backup(db_path: &Path, dest: &Path, on_progress: &mut dyn FnMut(i32, i32)) -> Result<()> {
+    let store = Storage::open(db_path)?;
+    store.backup_to(dest, on_progress)
+}
+
+/// Overwrite the database at `db_path` in place with the contents of `src`, via the same online
+/// backup mechanism as `backup`, reversed.
+pub fn restore(db_path: &Path, src: &Path, on_progress: &mut dyn FnMut(i32, i32)) -> Result<()> {
+    let mut store = Storage::open(db_path)?;
+    store.restore_from(src, on_progress)
+}
@@ -9,17 +9,39 @@ pub struct Setting {
     pub value: String,
 }
 
+/// A recognized setting's default value and accepted-value description, independent of what the
+/// database currently holds.
+pub struct SettingSchema {
+    pub name: String,
+    pub default: String,
+    pub type_hint: String,
+}
+
 pub fn run_config_list_all_settings(db_path: &Path) -> Result<Vec<Setting>> {
     let settings = get_all_settings(db_path)?;
+    Ok(convert_settings_to_output(&settings))
+}
 
-    Ok(settings
+/// Same as `run_config_list_all_settings`, but against a `Storage` the caller already has open,
+/// for callers (such as `cli::repl`) that hold a connection across several queries instead of
+/// reopening it each time.
+pub fn run_config_list_all_settings_with_store(store: &mut Storage) -> Result<Vec<Setting>> {
+    let mut tx = store.begin_transaction()?;
+    let settings = storage::setting::settings(&mut tx)?;
+    tx.commit()?;
+
+    Ok(convert_settings_to_output(&settings))
+}
+
+fn convert_settings_to_output(settings: &Settings) -> Vec<Setting> {
+    settings
         .list()
         .iter()
         .map(|s| Setting {
             name: s.name().to_owned(),
             value: s.as_str(),
         })
-        .collect())
+        .collect()
 }
 
 pub fn run_config_get_setting_value(db_path: &Path, name: &str) -> Result<String> {
@@ -44,6 +66,36 @@ pub fn run_config_update_setting(db_path: &Path, name: &str, value: &str) -> Res
     tx.commit()
 }
 
+/// Delete the stored row for `name` so the built-in default applies again on the next read.
+/// The name is validated against the recognized schema first.
+pub fn run_config_reset_setting(db_path: &Path, name: &str) -> Result<()> {
+    let defaults = Settings::new();
+    if defaults.get(name).is_none() {
+        return Err(format!("no such setting '{}'", name).into());
+    }
+
+    let mut store = Storage::open(&db_path)?;
+    let mut tx = store.begin_transaction()?;
+
+    storage::setting::delete_setting(&mut tx, name)?;
+
+    tx.commit()
+}
+
+/// List every recognized setting with its built-in default and accepted-value description, even
+/// when it is absent from the database.
+pub fn run_config_list_defaults() -> Vec<SettingSchema> {
+    Settings::new()
+        .list()
+        .iter()
+        .map(|s| SettingSchema {
+            name: s.name().to_owned(),
+            default: s.as_str(),
+            type_hint: s.type_hint().to_owned(),
+        })
+        .collect()
+}
+
 fn get_all_settings(db_path: &Path) -> Result<Settings> {
     let mut store = Storage::open(&db_path)?;
     let mut tx = store.begin_transaction()?;
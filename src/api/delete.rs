@@ -8,6 +8,8 @@ pub fn run_delete_tag(db_path: &Path, tag_names: &[&str]) -> Result<()> {
     let mut store = Storage::open(&db_path)?;
     let mut tx = store.begin_transaction()?;
 
+    let session = tx.begin_change_session()?;
+
     for name in tag_names {
         let tag = api::load_existing_tag(&mut tx, name)?;
 
@@ -17,6 +19,8 @@ pub fn run_delete_tag(db_path: &Path, tag_names: &[&str]) -> Result<()> {
             .map_err(|e| format!("could not delete tag '{}': {}", name, e))?;
     }
 
+    tx.capture_change_session(session, "delete tag")?;
+
     tx.commit()
 }
 
@@ -24,6 +28,8 @@ pub fn run_delete_value(db_path: &Path, value_names: &[&str]) -> Result<()> {
     let mut store = Storage::open(&db_path)?;
     let mut tx = store.begin_transaction()?;
 
+    let session = tx.begin_change_session()?;
+
     for name in value_names {
         let value = api::load_existing_value(&mut tx, name)?;
 
@@ -33,5 +39,7 @@ pub fn run_delete_value(db_path: &Path, value_names: &[&str]) -> Result<()> {
             .map_err(|e| format!("could not delete value '{}': {}", name, e))?;
     }
 
+    tx.capture_change_session(session, "delete value")?;
+
     tx.commit()
 }
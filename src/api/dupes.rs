@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::errors::*;
+use crate::path::ScopedPath;
+use crate::storage::{self, Storage, Transaction};
+
+/// A cluster of two or more files sharing the same non-empty fingerprint.
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub fingerprint: String,
+    pub paths: Vec<PathBuf>,
+}
+
+/// Find clusters of duplicate files, i.e. files whose stored fingerprints are identical.
+/// When `base_path` is given, only files stored under it are considered.
+pub fn run_dupes(db_path: &Path, base_path: &Option<PathBuf>) -> Result<Vec<DuplicateGroup>> {
+    let mut store = Storage::open(&db_path)?;
+    let root_path = store.root_path.clone();
+    let mut tx = store.begin_transaction()?;
+
+    let scoped_base_path = match base_path {
+        Some(path) => Some(ScopedPath::new(root_path, path)?),
+        None => None,
+    };
+
+    let groups = duplicate_groups(&mut tx, scoped_base_path.as_ref())?;
+
+    tx.commit()?;
+
+    Ok(groups)
+}
+
+/// Fold the fingerprint-ordered file list returned by storage into duplicate clusters.
+pub(crate) fn duplicate_groups(
+    tx: &mut Transaction,
+    base_path: Option<&ScopedPath>,
+) -> Result<Vec<DuplicateGroup>> {
+    info!("Retrieving duplicate files");
+
+    let files = storage::file::duplicate_files(tx, base_path)?;
+
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    for file in files {
+        // The files are ordered by fingerprint, so a run of identical fingerprints is contiguous.
+        match groups.last_mut() {
+            Some(group) if group.fingerprint == file.fingerprint => {
+                group.paths.push(file.to_path_buf());
+            }
+            _ => groups.push(DuplicateGroup {
+                fingerprint: file.fingerprint,
+                paths: vec![file.to_path_buf()],
+            }),
+        }
+    }
+
+    Ok(groups)
+}
@@ -1,5 +1,8 @@
 use std::path::Path;
 
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+
 use crate::entities;
 use crate::entities::FileSort;
 use crate::errors::*;
@@ -7,9 +10,12 @@ use crate::path::{AbsPath, CasedContains, IntoAbsPath, ScopedPath};
 use crate::query::Expression;
 use crate::storage::{self, Storage, Transaction};
 
+#[derive(Debug, Serialize)]
 pub struct FileData {
     pub path: AbsPath,
     pub is_dir: bool,
+    pub size: u64,
+    pub mod_time: DateTime<FixedOffset>,
 }
 
 pub fn list_matching(
@@ -17,10 +23,33 @@ pub fn list_matching(
     str_query: &str,
     explicit_only: bool,
     ignore_case: bool,
+    include_hierarchy: bool,
     path: Option<&Path>,
     file_sort: Option<FileSort>,
 ) -> Result<Vec<FileData>> {
     let mut store = Storage::open(&db_path)?;
+    list_matching_with_store(
+        &mut store,
+        str_query,
+        explicit_only,
+        ignore_case,
+        include_hierarchy,
+        path,
+        file_sort,
+    )
+}
+
+/// Same as `list_matching`, but against a `Storage` the caller already has open, for callers (such
+/// as `cli::repl`) that hold a connection across several queries instead of reopening it each time.
+pub fn list_matching_with_store(
+    store: &mut Storage,
+    str_query: &str,
+    explicit_only: bool,
+    ignore_case: bool,
+    include_hierarchy: bool,
+    path: Option<&Path>,
+    file_sort: Option<FileSort>,
+) -> Result<Vec<FileData>> {
     let root_path = store.root_path.clone();
     let mut tx = store.begin_transaction()?;
 
@@ -28,6 +57,20 @@ pub fn list_matching(
     let expr_opt: Option<Expression> = Expression::parse(str_query)?;
     debug!("Parsed query: {:?}", expr_opt);
 
+    // Inline any `:name` saved-query references before anything else looks at the expression.
+    let expr_opt = expr_opt
+        .map(|expr| {
+            expr.resolve_saved(
+                &mut |name| {
+                    storage::saved_query::saved_query_by_name(&mut tx, name)
+                        .map(|saved| saved.map(|s| s.query))
+                },
+                &mut vec![],
+            )
+        })
+        .transpose()?;
+    debug!("Resolved query: {:?}", expr_opt);
+
     // Sanity checks
     if let Some(ref expr) = expr_opt {
         check_tag_names(&mut tx, &expr, ignore_case)?;
@@ -46,6 +89,7 @@ pub fn list_matching(
         expr_opt.as_ref(),
         explicit_only,
         ignore_case,
+        include_hierarchy,
         scoped_base_path.as_ref(),
         file_sort,
     )?;
@@ -54,9 +98,16 @@ pub fn list_matching(
 
     Ok(files
         .into_iter()
-        .map(|f| FileData {
-            is_dir: f.is_dir,
-            path: f.into_abs_path(&*root_path),
+        .map(|f| {
+            let is_dir = f.is_dir;
+            let size = f.size;
+            let mod_time = f.mod_time;
+            FileData {
+                is_dir,
+                size,
+                mod_time,
+                path: f.into_abs_path(&*root_path),
+            }
         })
         .collect())
 }
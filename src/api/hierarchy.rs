@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::api;
+use crate::errors::*;
+use crate::storage::{self, Storage, Transaction};
+
+pub struct HierarchyListOutput {
+    pub containments: Vec<Containment>,
+}
+
+/// A single `parent HAS child` containment edge, rendered with tag names rather than ids.
+#[derive(Debug)]
+pub struct Containment {
+    pub parent: String,
+    pub child: String,
+}
+
+pub fn run_hierarchy_list(db_path: &Path) -> Result<HierarchyListOutput> {
+    let mut store = Storage::open(&db_path)?;
+    let mut tx = store.begin_transaction()?;
+
+    let hierarchies = storage::hierarchy::hierarchies(&mut tx)?;
+
+    tx.commit()?;
+
+    let containments = hierarchies
+        .into_iter()
+        .map(|h| Containment {
+            parent: h.parent.name,
+            child: h.child.name,
+        })
+        .collect();
+
+    Ok(HierarchyListOutput { containments })
+}
+
+pub fn add_hierarchies(db_path: &Path, containments: &[Containment]) -> Result<()> {
+    let mut store = Storage::open(&db_path)?;
+    let mut tx = store.begin_transaction()?;
+
+    info!("Loading settings");
+    let settings = storage::setting::settings(&mut tx)?;
+
+    // The containment edges form a directed graph; reject the batch if adding them would introduce
+    // a cycle, which would make transitive descendant queries expand forever.
+    ensure_no_cycles(&mut tx, containments)?;
+
+    for containment in containments {
+        let parent = api::load_or_create_tag(&mut tx, &containment.parent, &settings)?;
+        let child = api::load_or_create_tag(&mut tx, &containment.child, &settings)?;
+
+        info!(
+            "Adding tag containment: '{}' HAS '{}'",
+            &containment.parent, &containment.child
+        );
+
+        storage::hierarchy::add_hierarchy(&mut tx, &parent.id, &child.id).map_err(|e| {
+            format!(
+                "could not add containment of '{}' in '{}': {}",
+                &containment.child, &containment.parent, e
+            )
+        })?;
+    }
+
+    tx.commit()
+}
+
+pub fn delete_hierarchies(db_path: &Path, containments: &[Containment]) -> Result<()> {
+    let mut store = Storage::open(&db_path)?;
+    let mut tx = store.begin_transaction()?;
+
+    for containment in containments {
+        info!(
+            "Removing tag containment: '{}' HAS '{}'",
+            &containment.parent, &containment.child
+        );
+
+        let parent = api::load_existing_tag(&mut tx, &containment.parent)?;
+        let child = api::load_existing_tag(&mut tx, &containment.child)?;
+
+        storage::hierarchy::delete_hierarchy(&mut tx, &parent.id, &child.id).map_err(|e| {
+            format!(
+                "could not delete containment of '{}' in '{}': {}",
+                &containment.child, &containment.parent, e
+            )
+        })?;
+    }
+
+    tx.commit()
+}
+
+/// Load the containment edges as a directed graph keyed by tag name.
+fn load_graph(tx: &mut Transaction) -> Result<HashMap<String, Vec<String>>> {
+    let mut graph: HashMap<String, Vec<String>> = HashMap::new();
+    for hierarchy in storage::hierarchy::hierarchies(tx)? {
+        graph
+            .entry(hierarchy.parent.name)
+            .or_default()
+            .push(hierarchy.child.name);
+    }
+    Ok(graph)
+}
+
+/// Run a white/grey/black DFS over the existing containments plus the proposed new edges, starting
+/// from each new parent. Returns an error naming the offending path if a cycle is found.
+fn ensure_no_cycles(tx: &mut Transaction, new: &[Containment]) -> Result<()> {
+    let mut graph = load_graph(tx)?;
+    for containment in new {
+        graph
+            .entry(containment.parent.clone())
+            .or_default()
+            .push(containment.child.clone());
+    }
+
+    for containment in new {
+        let mut colors = HashMap::new();
+        let mut path = Vec::new();
+        if let Some(cycle) = find_cycle(&graph, &containment.parent, &mut colors, &mut path) {
+            return Err(format!("containment would create a cycle: {}", cycle.join(" -> ")).into());
+        }
+    }
+
+    Ok(())
+}
+
+enum Color {
+    Grey,
+    Black,
+}
+
+fn find_cycle(
+    graph: &HashMap<String, Vec<String>>,
+    node: &str,
+    colors: &mut HashMap<String, Color>,
+    path: &mut Vec<String>,
+) -> Option<Vec<String>> {
+    colors.insert(node.to_owned(), Color::Grey);
+    path.push(node.to_owned());
+
+    if let Some(neighbors) = graph.get(node) {
+        for next in neighbors {
+            match colors.get(next) {
+                Some(Color::Grey) => {
+                    // Back-edge to a node still on the stack: reconstruct the cycle path.
+                    let start = path.iter().position(|n| n == next).unwrap_or(0);
+                    let mut cycle = path[start..].to_vec();
+                    cycle.push(next.clone());
+                    return Some(cycle);
+                }
+                Some(Color::Black) => {}
+                None => {
+                    if let Some(cycle) = find_cycle(graph, next, colors, path) {
+                        return Some(cycle);
+                    }
+                }
+            }
+        }
+    }
+
+    path.pop();
+    colors.insert(node.to_owned(), Color::Black);
+    None
+}
@@ -1,9 +1,11 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::fmt;
+use std::fs;
+use std::io::Write;
 use std::path::Path;
 
 use crate::api;
-use crate::entities::{self, OptionalValueId, ValueId};
+use crate::entities::{self, OptionalValueId};
 use crate::errors::*;
 use crate::storage::{self, Storage, Transaction};
 
@@ -34,6 +36,13 @@ impl fmt::Display for TagAndOptionalValue {
 
 pub fn run_imply_list(db_path: &Path) -> Result<ImplyListOutput> {
     let mut store = Storage::open(&db_path)?;
+    run_imply_list_with_store(&mut store)
+}
+
+/// Same as `run_imply_list`, but against a `Storage` the caller already has open, for callers
+/// (such as `cli::repl`) that hold a connection across several queries instead of reopening it
+/// each time.
+pub fn run_imply_list_with_store(store: &mut Storage) -> Result<ImplyListOutput> {
     let mut tx = store.begin_transaction()?;
 
     let implications = storage::implication::implications(&mut tx)?;
@@ -60,6 +69,40 @@ fn convert_to_output(implication: entities::Implication) -> Implication {
     }
 }
 
+#[derive(Debug)]
+pub struct CompoundImplication {
+    pub antecedents: Vec<TagAndOptionalValue>,
+    pub implied: TagAndOptionalValue,
+}
+
+pub fn run_compound_imply_list(db_path: &Path) -> Result<Vec<CompoundImplication>> {
+    let mut store = Storage::open(&db_path)?;
+    let mut tx = store.begin_transaction()?;
+
+    let rules = storage::implication::compound_implications_named(&mut tx)?;
+
+    tx.commit()?;
+
+    Ok(rules.into_iter().map(convert_compound_to_output).collect())
+}
+
+fn convert_compound_to_output(rule: entities::CompoundImplicationNamed) -> CompoundImplication {
+    CompoundImplication {
+        antecedents: rule
+            .antecedents
+            .into_iter()
+            .map(|(tag, value)| TagAndOptionalValue {
+                tag_name: tag.name,
+                value_name: value.map(|v| v.name),
+            })
+            .collect(),
+        implied: TagAndOptionalValue {
+            tag_name: rule.implied.0.name,
+            value_name: rule.implied.1.map(|v| v.name),
+        },
+    }
+}
+
 pub fn delete_implications(db_path: &Path, implications: &[Implication]) -> Result<()> {
     let mut store = Storage::open(&db_path)?;
     let mut tx = store.begin_transaction()?;
@@ -83,6 +126,9 @@ pub fn delete_implications(db_path: &Path, implications: &[Implication]) -> Resu
         )?;
     }
 
+    // Keep the materialized closure in step with the edge that was just removed.
+    storage::implication::rebuild_closure(&mut tx)?;
+
     tx.commit()
 }
 
@@ -110,6 +156,10 @@ pub fn add_implications(db_path: &Path, implications: &[Implication]) -> Result<
     info!("Loading settings");
     let settings = storage::setting::settings(&mut tx)?;
 
+    // Treat the implications as a directed graph and reject the whole batch if adding the new
+    // edges would introduce a cycle, which would otherwise expand forever at query time.
+    ensure_no_cycles(&mut tx, implications)?;
+
     for implication in implications {
         let implying_pair =
             convert_to_id_pair_may_create(&mut tx, &implication.implying, &settings)?;
@@ -120,30 +170,301 @@ pub fn add_implications(db_path: &Path, implications: &[Implication]) -> Result<
             &implication.implying, &implication.implied
         );
 
-        add_single_implication(&mut tx, &implying_pair, &implied_pair).map_err(|e| {
+        storage::implication::add_implication(&mut tx, &implying_pair, &implied_pair).map_err(
+            |e| {
+                format!(
+                    "could not add implication of '{}' to '{}': {}",
+                    &implication.implying, implication.implied, e
+                )
+            },
+        )?;
+    }
+
+    // Keep the materialized closure in step with the edges that were just added.
+    storage::implication::rebuild_closure(&mut tx)?;
+
+    tx.commit()
+}
+
+/// Add a conjunctive implication rule (e.g. `a AND b=2 => c`): the implied tag is only inferred for
+/// a file that carries every one of `antecedents`. Rejected, like `add_implications`, if it would
+/// introduce a cycle.
+///
+/// Unlike single-antecedent rules, conjunctive rules aren't folded into the materialized
+/// `implication_closure` table, so they aren't used to speed up tag-name queries the way simple
+/// implications are; they are only evaluated (via `storage::meta::add_implied_file_tags`) when
+/// listing a file's tags.
+pub fn add_compound_implication(
+    db_path: &Path,
+    antecedents: &[TagAndOptionalValue],
+    implied: &TagAndOptionalValue,
+) -> Result<()> {
+    let mut store = Storage::open(&db_path)?;
+    let mut tx = store.begin_transaction()?;
+
+    info!("Loading settings");
+    let settings = storage::setting::settings(&mut tx)?;
+
+    ensure_compound_rule_acyclic(&mut tx, antecedents, implied)?;
+
+    let antecedent_pairs = antecedents
+        .iter()
+        .map(|tag_and_value| convert_to_id_pair_may_create(&mut tx, tag_and_value, &settings))
+        .collect::<Result<Vec<_>>>()?;
+    let implied_pair = convert_to_id_pair_may_create(&mut tx, implied, &settings)?;
+
+    let antecedents_str = antecedents
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    info!(
+        "Adding compound tag implication: '{}' -> '{}'",
+        antecedents_str, implied
+    );
+
+    storage::implication::add_compound_implication(&mut tx, &antecedent_pairs, &implied_pair)
+        .map_err(|e| {
             format!(
                 "could not add implication of '{}' to '{}': {}",
-                &implication.implying, implication.implied, e
+                antecedents_str, implied, e
             )
         })?;
-    }
 
     tx.commit()
 }
 
-/// Simple auxiliary function, used only to avoid duplicating the "map_err" in the calling code.
-fn add_single_implication(
+/// Like `ensure_no_cycles`, but for a single rule with (possibly several) conjunctive antecedents:
+/// each antecedent gets its own edge straight to `implied` (see `load_graph`), and each must be
+/// checked as a potential cycle start in turn.
+fn ensure_compound_rule_acyclic(
     tx: &mut Transaction,
-    implying_pair: &entities::TagIdValueIdPair,
-    implied_pair: &entities::TagIdValueIdPair,
+    antecedents: &[TagAndOptionalValue],
+    implied: &TagAndOptionalValue,
 ) -> Result<()> {
-    check_for_implication_cycles(tx, &implying_pair, &implied_pair)?;
+    let mut graph = load_graph(tx)?;
+    for antecedent in antecedents {
+        graph
+            .entry(antecedent.to_string())
+            .or_default()
+            .push(implied.to_string());
+    }
 
-    storage::implication::add_implication(tx, &implying_pair, &implied_pair)?;
+    for antecedent in antecedents {
+        let mut colors = HashMap::new();
+        let mut path = Vec::new();
+        if let Some(cycle) = find_cycle(&graph, &antecedent.to_string(), &mut colors, &mut path) {
+            return Err(format!("implication would create a cycle: {}", cycle.join(" -> ")).into());
+        }
+    }
 
     Ok(())
 }
 
+/// Write every single-antecedent implication rule to `path`, one rule per line in the same
+/// `implying[=value] -> implied[=value]` format the CLI prints, so exports round-trip through
+/// `import_implications`. Conjunctive (multi-antecedent) rules are not written.
+pub fn export_implications(db_path: &Path, path: &Path) -> Result<()> {
+    let output = run_imply_list(db_path)?;
+
+    let mut file = fs::File::create(path)
+        .map_err(|e| format!("{}: could not create file: {}", path.display(), e))?;
+    for implication in &output.implications {
+        writeln!(file, "{} -> {}", implication.implying, implication.implied)
+            .map_err(|e| format!("{}: could not write file: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+/// Read a ruleset previously written by `export_implications` and apply it in a single
+/// transaction, validating it exactly as interactive adds do (including the cycle check). A
+/// malformed line aborts the whole batch before anything is written.
+pub fn import_implications(db_path: &Path, path: &Path) -> Result<()> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("{}: could not read file: {}", path.display(), e))?;
+
+    let mut implications = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        implications
+            .push(parse_implication_line(line).map_err(|e| format!("line {}: {}", index + 1, e))?);
+    }
+
+    add_implications(db_path, &implications)
+}
+
+fn parse_implication_line(line: &str) -> Result<Implication> {
+    match line.splitn(2, "->").collect::<Vec<_>>()[..] {
+        [implying, implied] => Ok(Implication {
+            implying: parse_tag_and_value(implying.trim()),
+            implied: parse_tag_and_value(implied.trim()),
+        }),
+        _ => Err(format!("expected 'implying -> implied', got '{}'", line).into()),
+    }
+}
+
+fn parse_tag_and_value(text: &str) -> TagAndOptionalValue {
+    match text.splitn(2, '=').collect::<Vec<_>>()[..] {
+        [tag, value] => TagAndOptionalValue {
+            tag_name: tag.to_owned(),
+            value_name: Some(value.to_owned()),
+        },
+        _ => TagAndOptionalValue {
+            tag_name: text.to_owned(),
+            value_name: None,
+        },
+    }
+}
+
+/// Walk the transitive closure of the implications rooted at `src`, listing every tag a file would
+/// implicitly receive. Each entry carries its BFS depth so the caller can indent accordingly.
+pub fn run_imply_tree(db_path: &Path, src: &TagAndOptionalValue) -> Result<Vec<(usize, String)>> {
+    let mut store = Storage::open(&db_path)?;
+    let mut tx = store.begin_transaction()?;
+
+    let mut graph: HashMap<String, Vec<String>> = HashMap::new();
+    for implication in storage::implication::implications(&mut tx)? {
+        let from = node_key(&implication.implying_tag.name, &implication.implying_value);
+        let to = node_key(&implication.implied_tag.name, &implication.implied_value);
+        graph.entry(from).or_default().push(to);
+    }
+    let rules = storage::implication::compound_implications_named(&mut tx)?;
+
+    tx.commit()?;
+
+    let mut lines = Vec::new();
+    let mut visited = vec![src.to_string()];
+    let mut frontier = vec![src.to_string()];
+    let mut depth = 0;
+
+    while !frontier.is_empty() {
+        depth += 1;
+        let mut next_frontier = Vec::new();
+        for node in &frontier {
+            if let Some(neighbors) = graph.get(node) {
+                for neighbor in neighbors {
+                    if !visited.contains(neighbor) {
+                        visited.push(neighbor.clone());
+                        lines.push((depth, neighbor.clone()));
+                        next_frontier.push(neighbor.clone());
+                    }
+                }
+            }
+        }
+        for rule in &rules {
+            let to = node_key(&rule.implied.0.name, &rule.implied.1);
+            let fires = rule.antecedents.iter().all(|(t, v)| visited.contains(&node_key(&t.name, v)));
+            if fires && !visited.contains(&to) {
+                visited.push(to.clone());
+                lines.push((depth, to.clone()));
+                next_frontier.push(to);
+            }
+        }
+        frontier = next_frontier;
+    }
+
+    Ok(lines)
+}
+
+/// Load the implication ruleset as a directed graph keyed by the `tag[=value]` rendering of each
+/// node.
+///
+/// A conjunctive rule only fires once *every* one of its antecedents is present, but for cycle
+/// detection it's enough to treat each antecedent as able, on its own, to reach the consequent: if
+/// the consequent can transitively reach back to any one antecedent, the ruleset is
+/// self-referential, exactly as for a single-antecedent rule. So each antecedent contributes its
+/// own edge straight to the consequent.
+fn load_graph(tx: &mut Transaction) -> Result<HashMap<String, Vec<String>>> {
+    let mut graph: HashMap<String, Vec<String>> = HashMap::new();
+    for implication in storage::implication::implications(tx)? {
+        let from = node_key(&implication.implying_tag.name, &implication.implying_value);
+        let to = node_key(&implication.implied_tag.name, &implication.implied_value);
+        graph.entry(from).or_default().push(to);
+    }
+
+    for rule in storage::implication::compound_implications_named(tx)? {
+        let to = node_key(&rule.implied.0.name, &rule.implied.1);
+        for (tag, value) in &rule.antecedents {
+            let from = node_key(&tag.name, value);
+            graph.entry(from).or_default().push(to.clone());
+        }
+    }
+
+    Ok(graph)
+}
+
+fn node_key(tag_name: &str, value: &Option<entities::Value>) -> String {
+    match value {
+        None => tag_name.to_owned(),
+        Some(v) => format!("{}={}", tag_name, v.name),
+    }
+}
+
+/// Run a white/grey/black DFS over the existing implications plus the proposed new edges, starting
+/// from each new source node. Returns an error naming the offending path if a cycle is found.
+fn ensure_no_cycles(tx: &mut Transaction, new: &[Implication]) -> Result<()> {
+    let mut graph = load_graph(tx)?;
+    for implication in new {
+        graph
+            .entry(implication.implying.to_string())
+            .or_default()
+            .push(implication.implied.to_string());
+    }
+
+    for implication in new {
+        let mut colors = HashMap::new();
+        let mut path = Vec::new();
+        if let Some(cycle) = find_cycle(&graph, &implication.implying.to_string(), &mut colors, &mut path) {
+            return Err(format!("implication would create a cycle: {}", cycle.join(" -> ")).into());
+        }
+    }
+
+    Ok(())
+}
+
+enum Color {
+    Grey,
+    Black,
+}
+
+fn find_cycle(
+    graph: &HashMap<String, Vec<String>>,
+    node: &str,
+    colors: &mut HashMap<String, Color>,
+    path: &mut Vec<String>,
+) -> Option<Vec<String>> {
+    colors.insert(node.to_owned(), Color::Grey);
+    path.push(node.to_owned());
+
+    if let Some(neighbors) = graph.get(node) {
+        for next in neighbors {
+            match colors.get(next) {
+                Some(Color::Grey) => {
+                    // Back-edge to a node still on the stack: reconstruct the cycle path.
+                    let start = path.iter().position(|n| n == next).unwrap_or(0);
+                    let mut cycle = path[start..].to_vec();
+                    cycle.push(next.clone());
+                    return Some(cycle);
+                }
+                Some(Color::Black) => {}
+                None => {
+                    if let Some(cycle) = find_cycle(graph, next, colors, path) {
+                        return Some(cycle);
+                    }
+                }
+            }
+        }
+    }
+
+    path.pop();
+    colors.insert(node.to_owned(), Color::Black);
+    None
+}
+
 fn convert_to_id_pair_may_create(
     tx: &mut Transaction,
     tag_and_value: &TagAndOptionalValue,
@@ -162,56 +483,3 @@ fn convert_to_id_pair_may_create(
     })
 }
 
-fn check_for_implication_cycles(
-    tx: &mut Transaction,
-    implying: &entities::TagIdValueIdPair,
-    implied: &entities::TagIdValueIdPair,
-) -> Result<()> {
-    let implications = transitive_implications_for(tx, implied)?;
-
-    for implication in implications {
-        if implication.implied_tag.id == implying.tag_id
-            && (implying.value_id.is_none()
-                || equal_values(&implying.value_id, &implication.implied_value))
-        {
-            return Err("implication would create a cycle".into());
-        }
-    }
-    Ok(())
-}
-
-fn transitive_implications_for(
-    tx: &mut Transaction,
-    initial_pair: &entities::TagIdValueIdPair,
-) -> Result<Vec<entities::Implication>> {
-    let mut resultant_implications = HashSet::new();
-
-    let mut to_process = vec![initial_pair.clone()];
-
-    while !to_process.is_empty() {
-        let implications = storage::implication::implications_for(tx, &to_process)?;
-
-        to_process = Vec::new();
-        for implication in implications {
-            if !resultant_implications.contains(&implication) {
-                to_process.push(entities::TagIdValueIdPair {
-                    tag_id: implication.implied_tag.id,
-                    value_id: OptionalValueId::from_opt_value(&implication.implied_value),
-                });
-                resultant_implications.insert(implication);
-            }
-        }
-    }
-
-    Ok(resultant_implications.into_iter().collect())
-}
-
-fn equal_values(val1: &Option<ValueId>, val2: &Option<entities::Value>) -> bool {
-    if let Some(id1) = *val1 {
-        if let Some(v2) = val2 {
-            return id1 == v2.id;
-        }
-    }
-
-    false
-}
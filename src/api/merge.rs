@@ -11,6 +11,8 @@ pub fn run_merge_tags(db_path: &Path, source_names: &[&str], dest_name: &str) ->
 
     let dest_tag = api::load_existing_tag(&mut tx, dest_name)?;
 
+    let session = tx.begin_change_session()?;
+
     for source_name in source_names {
         if *source_name == dest_name {
             return Err(format!("cannot merge tag '{}' into itself", source_name).into());
@@ -42,6 +44,8 @@ pub fn run_merge_tags(db_path: &Path, source_names: &[&str], dest_name: &str) ->
             .map_err(|e| format!("could not delete tag '{}': {}", source_name, e))?;
     }
 
+    tx.capture_change_session(session, "merge tags")?;
+
     tx.commit()
 }
 
@@ -51,6 +55,8 @@ pub fn run_merge_values(db_path: &Path, source_names: &[&str], dest_name: &str)
 
     let dest_value = api::load_existing_value(&mut tx, dest_name)?;
 
+    let session = tx.begin_change_session()?;
+
     for source_name in source_names {
         if *source_name == dest_name {
             return Err(format!("cannot merge value '{}' into itself", source_name).into());
@@ -82,5 +88,7 @@ pub fn run_merge_values(db_path: &Path, source_names: &[&str], dest_name: &str)
             .map_err(|e| format!("could not delete value '{}': {}", source_name, e))?;
     }
 
+    tx.capture_change_session(session, "merge values")?;
+
     tx.commit()
 }
@@ -25,12 +25,14 @@ pub fn run_rename_tag(db_path: &Path, curr_name: &str, new_name: &str) -> Result
 
     info!("Renaming tag '{}' to '{}'", curr_name, new_name);
 
+    let session = tx.begin_change_session()?;
     map_err(
         storage::tag::rename_tag(&mut tx, &curr_tag.id, new_name),
         "rename tag",
         curr_name,
         new_name,
     )?;
+    tx.capture_change_session(session, "rename tag")?;
 
     tx.commit()
 }
@@ -53,12 +55,14 @@ pub fn run_rename_value(db_path: &Path, curr_name: &str, new_name: &str) -> Resu
 
     info!("Renaming value '{}' to '{}'", curr_name, new_name);
 
+    let session = tx.begin_change_session()?;
     map_err(
         storage::value::rename_value(&mut tx, &curr_value.id, new_name),
         "rename value",
         curr_name,
         new_name,
     )?;
+    tx.capture_change_session(session, "rename value")?;
 
     tx.commit()
 }
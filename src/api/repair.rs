@@ -1,8 +1,9 @@
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-use chrono::{DateTime, FixedOffset, Utc};
+use chrono::{DateTime, Duration, Utc};
 
 use crate::entities::{settings::Settings, File};
 use crate::errors::*;
@@ -10,49 +11,115 @@ use crate::fingerprint;
 use crate::path::{CanonicalPath, IntoAbsPath, ScopedPath};
 use crate::storage::{self, Storage, Transaction};
 
+/// Relocate stored file paths according to a list of `OLD=NEW` mappings, all in one transaction.
+///
+/// In prefix mode, every stored path that begins with an OLD prefix is rewritten to begin with the
+/// corresponding NEW prefix; mappings are tried longest-OLD-first so the most specific root wins.
+/// In regex mode, OLD is a regular expression matched against the whole stored path and NEW is a
+/// replacement template with capturing-group substitution; the first matching mapping wins.
+///
+/// A file's fingerprint and metadata are only refreshed when the rewritten path exists on disk;
+/// otherwise the record is simply relocated. With `pretend` set, the full rewrite plan is logged
+/// but nothing is committed.
 pub fn manual_repair(
     db_path: &Path,
-    from_path: &Path,
-    to_path: &Path,
+    mappings: &[(String, String)],
+    use_regex: bool,
     pretend: bool,
 ) -> Result<()> {
     let mut store = Storage::open(&db_path)?;
     let root_path = store.root_path.clone();
     let mut tx = store.begin_transaction()?;
 
-    let scoped_from_path = ScopedPath::new(root_path.clone(), from_path)?;
-    let scoped_to_path = ScopedPath::new(root_path, to_path)?;
-
     info!("Loading settings");
     let settings = storage::setting::settings(&mut tx)?;
 
-    info!(
-        "Retrieving files under '{}' from the database",
-        from_path.display()
-    );
+    let rewriter = PathRewriter::new(mappings, use_regex)?;
 
-    let from_file_opt = storage::file::file_by_path(&mut tx, &scoped_from_path)
-        .map_err(|e| format!("{}: could not retrieve file: {}", from_path.display(), e))?;
+    info!("Retrieving all files from the database");
+    let db_files = storage::file::files(&mut tx, crate::entities::FileSort::Name)
+        .map_err(|e| format!("could not retrieve files from storage: {}", e))?;
 
-    if let Some(db_file) = from_file_opt {
-        info!("{}: updating to {}", from_path.display(), to_path.display());
+    // Track which OLD patterns actually relocated a file. A mapping that matches nothing was
+    // requested explicitly by the user but names a path absent from the database, so we report it
+    // rather than succeeding silently.
+    let mut matched: HashSet<&str> = HashSet::new();
 
-        if !pretend {
-            manual_repair_file(&mut tx, &settings, &db_file, &scoped_to_path)?;
+    for db_file in &db_files {
+        let old_path = db_file.to_path_buf();
+        let old_path_str = old_path.to_string_lossy();
+
+        if let Some((old, new_path_str)) = rewriter.rewrite(&old_path_str) {
+            matched.insert(old);
+            let new_path = PathBuf::from(new_path_str);
+            info!("{}: updating to {}", old_path.display(), new_path.display());
+
+            if !pretend {
+                let scoped_to_path = ScopedPath::new(root_path.clone(), &new_path)?;
+                manual_repair_file(&mut tx, &settings, db_file, &scoped_to_path)?;
+            }
         }
     }
 
-    let db_files = storage::file::files_by_directory(&mut tx, &scoped_from_path)
-        .map_err(|e| format!("could not retrieve files from storage: {}", e))?;
+    for (old, _) in mappings {
+        if !matched.contains(old.as_str()) {
+            return Err(
+                format!("{}: no tagged files match this path mapping", old).into(),
+            );
+        }
+    }
 
-    for db_file in db_files {
-        info!("{}: updating to {}", from_path.display(), to_path.display());
-        if !pretend {
-            manual_repair_file(&mut tx, &settings, &db_file, &scoped_to_path)?;
+    tx.commit()
+}
+
+/// Rewrites stored paths using either literal prefix mappings or regular expressions.
+enum PathRewriter {
+    /// Prefix mappings, sorted so the longest OLD prefix is tried first.
+    Prefix(Vec<(String, String)>),
+    /// Compiled regular expressions paired with their replacement templates, tried in order.
+    Regex(Vec<(regex::Regex, String)>),
+}
+
+impl PathRewriter {
+    fn new(mappings: &[(String, String)], use_regex: bool) -> Result<Self> {
+        if use_regex {
+            let mut compiled = Vec::with_capacity(mappings.len());
+            for (old, new) in mappings {
+                let re = regex::Regex::new(old)
+                    .map_err(|e| format!("invalid regular expression '{}': {}", old, e))?;
+                compiled.push((re, new.clone()));
+            }
+            Ok(PathRewriter::Regex(compiled))
+        } else {
+            let mut sorted = mappings.to_vec();
+            sorted.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+            Ok(PathRewriter::Prefix(sorted))
         }
     }
 
-    Ok(())
+    /// Return the matched OLD pattern together with the rewritten path, or `None` if no mapping
+    /// applies. The OLD pattern is reported so the caller can tell which user-supplied mappings
+    /// actually relocated a file.
+    fn rewrite(&self, path: &str) -> Option<(&str, String)> {
+        match self {
+            PathRewriter::Prefix(mappings) => {
+                for (old, new) in mappings {
+                    if path.starts_with(old.as_str()) {
+                        return Some((old, format!("{}{}", new, &path[old.len()..])));
+                    }
+                }
+                None
+            }
+            PathRewriter::Regex(mappings) => {
+                for (re, template) in mappings {
+                    if re.is_match(path) {
+                        return Some((re.as_str(), re.replace(path, template.as_str()).into_owned()));
+                    }
+                }
+                None
+            }
+        }
+    }
 }
 
 fn manual_repair_file(
@@ -63,7 +130,10 @@ fn manual_repair_file(
 ) -> Result<()> {
     // Note: unlike the Go implementation, we don't check for permissions issues
     if !to_path.exists() {
-        return Err(format!("{}: file not found", to_path.display()).into());
+        // The file isn't present at the new location, so keep the existing fingerprint and
+        // metadata and merely relocate the record.
+        storage::file::update_file_path(tx, &db_file.id, to_path)?;
+        return Ok(());
     }
 
     // Note: unlike the Go implementation, failing to create the fingerprint is fatal
@@ -131,6 +201,18 @@ pub fn full_repair(
         scoped_base_path.display()
     );
 
+    // A path named explicitly on the command line must resolve to something tagged: either the
+    // path itself or a directory with tagged children. An empty result means the user asked to
+    // repair a path that the database knows nothing about, which is an error rather than a no-op.
+    // When no `--path` is given we repair the whole database and tolerate empty subtrees.
+    if path.is_some() && db_files.is_empty() {
+        return Err(format!(
+            "{}: path is not tagged and has no tagged files beneath it",
+            scoped_base_path.display()
+        )
+        .into());
+    }
+
     let statuses = determine_statuses(&db_files, root_path.clone())?;
 
     if recalc_unmodified {
@@ -168,6 +250,17 @@ pub fn full_repair(
         rationalize_file_tags(&mut tx, &db_files)?;
     }
 
+    if settings.report_duplicates() {
+        report_duplicates(&mut tx, &scoped_base_path)?;
+    }
+
+    // Recompute the materialized implication closure, which also creates it for databases that
+    // predate the table. This is the supported way to populate the closure for an existing
+    // database: running `repair` once brings an old database up to date.
+    if !pretend {
+        storage::implication::rebuild_closure(&mut tx)?;
+    }
+
     tx.commit()
 }
 
@@ -180,6 +273,8 @@ fn repair_unmodified(
 ) -> Result<()> {
     info!("Recalculating fingerprints for unmodified files");
 
+    let status_start: DateTime<Utc> = Utc::now();
+
     for db_file in unmodified {
         let scoped_path = ScopedPath::new(root_path.clone(), db_file.to_path_buf())?;
 
@@ -188,12 +283,15 @@ fn repair_unmodified(
         let mod_time_utc: DateTime<Utc> = mod_time.into();
 
         // Note: unlike the Go implementation, failing to create the fingerprint is fatal
-        let fingerprint = fingerprint::create(
-            &scoped_path,
-            &settings.file_fingerprint_algorithm()?,
-            &settings.directory_fingerprint_algorithm()?,
-            &settings.symlink_fingerprint_algorithm()?,
-        )?;
+        let fingerprint = match cached_directory_fingerprint(db_file, &scoped_path, status_start)? {
+            Some(fingerprint) => fingerprint,
+            None => fingerprint::create(
+                &scoped_path,
+                &settings.file_fingerprint_algorithm()?,
+                &settings.directory_fingerprint_algorithm()?,
+                &settings.symlink_fingerprint_algorithm()?,
+            )?,
+        };
 
         if !pretend {
             storage::file::update_file(
@@ -229,6 +327,8 @@ fn repair_modified(
 ) -> Result<()> {
     info!("Repairing modified files");
 
+    let status_start: DateTime<Utc> = Utc::now();
+
     for db_file in modified {
         let scoped_path = ScopedPath::new(root_path.clone(), db_file.to_path_buf())?;
 
@@ -237,12 +337,15 @@ fn repair_modified(
         let mod_time_utc: DateTime<Utc> = mod_time.into();
 
         // Note: unlike the Go implementation, failing to create the fingerprint is fatal
-        let fingerprint = fingerprint::create(
-            &scoped_path,
-            &settings.file_fingerprint_algorithm()?,
-            &settings.directory_fingerprint_algorithm()?,
-            &settings.symlink_fingerprint_algorithm()?,
-        )?;
+        let fingerprint = match cached_directory_fingerprint(db_file, &scoped_path, status_start)? {
+            Some(fingerprint) => fingerprint,
+            None => fingerprint::create(
+                &scoped_path,
+                &settings.file_fingerprint_algorithm()?,
+                &settings.directory_fingerprint_algorithm()?,
+                &settings.symlink_fingerprint_algorithm()?,
+            )?,
+        };
 
         if !pretend {
             storage::file::update_file(
@@ -270,6 +373,11 @@ fn repair_modified(
     Ok(())
 }
 
+// An earlier revision of this function tried a dev/ino fast path ahead of the size+fingerprint
+// search below, to skip fingerprinting a candidate whose (dev, ino) matched a missing file's
+// stored one. It was reverted: neither the `file` table nor the `File` entity carries a device or
+// inode number, so the fast path had nothing to match against and could never fire. Revisiting it
+// requires storing those numbers at tag/repair time first.
 fn repair_moved(
     tx: &mut Transaction,
     missing: &[&File],
@@ -287,6 +395,20 @@ fn repair_moved(
 
     let paths_by_size = build_paths_by_size_map(search_paths)?;
 
+    // Candidates already tracked in the database cannot be the new home of a missing file. Fetch
+    // the whole set once rather than issuing a `file_by_path` query per candidate inside the hot
+    // loop below.
+    let tracked_paths: HashSet<PathBuf> = storage::file::files(tx, crate::entities::FileSort::Name)?
+        .into_iter()
+        .map(|f| f.to_path_buf().into_abs_path(&*root_path))
+        .collect();
+
+    // Fingerprint index keyed by size, computed lazily on first use: the inner map reverses
+    // candidate fingerprint -> path so that resolving a missing file of a known size is a single
+    // hash lookup. Each candidate is therefore fingerprinted at most once, even when many missing
+    // files share its size (e.g. a whole directory moved wholesale).
+    let mut fingerprints_by_size: HashMap<u64, HashMap<String, PathBuf>> = HashMap::new();
+
     for db_file in missing {
         let abs_db_file = db_file.to_path_buf().into_abs_path(&*root_path);
         debug!("{}: searching for new location", abs_db_file.display());
@@ -299,46 +421,58 @@ fn repair_moved(
                 paths_of_size.len()
             );
 
-            for candidate_path in paths_of_size {
-                let scoped_candidate = ScopedPath::new(root_path.clone(), &candidate_path)?;
-                let candidate_file = storage::file::file_by_path(tx, &scoped_candidate)?;
-                if candidate_file.is_some() {
-                    // The file is already tagged
-                    continue;
+            // Build (or reuse) the reverse fingerprint index for every untracked candidate of
+            // this size. The first missing file of a given size pays to fingerprint the
+            // candidates once; all later missing files of the same size resolve with a single
+            // hash lookup.
+            let index = match fingerprints_by_size.entry(db_file.size) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let mut by_fingerprint = HashMap::new();
+                    for candidate_path in paths_of_size {
+                        if tracked_paths.contains(candidate_path) {
+                            // The file is already tagged
+                            continue;
+                        }
+
+                        let fingerprint = fingerprint::create(
+                            candidate_path,
+                            &settings.file_fingerprint_algorithm()?,
+                            &settings.directory_fingerprint_algorithm()?,
+                            &settings.symlink_fingerprint_algorithm()?,
+                        )?;
+
+                        by_fingerprint
+                            .entry(fingerprint)
+                            .or_insert_with(|| candidate_path.clone());
+                    }
+                    entry.insert(by_fingerprint)
                 }
+            };
 
+            if let Some(candidate_path) = index.get(&db_file.fingerprint) {
+                let scoped_candidate = ScopedPath::new(root_path.clone(), candidate_path)?;
                 let metadata = candidate_path.metadata()?;
                 let mod_time = metadata.modified()?;
                 let mod_time_utc: DateTime<Utc> = mod_time.into();
 
-                let fingerprint = fingerprint::create(
-                    &candidate_path,
-                    &settings.file_fingerprint_algorithm()?,
-                    &settings.directory_fingerprint_algorithm()?,
-                    &settings.symlink_fingerprint_algorithm()?,
-                )?;
-
-                if fingerprint == db_file.fingerprint {
-                    if !pretend {
-                        storage::file::update_file(
-                            tx,
-                            &db_file.id,
-                            &scoped_candidate,
-                            fingerprint,
-                            mod_time_utc.into(),
-                            db_file.size,
-                            db_file.is_dir,
-                        )?;
-                    }
-
-                    println!(
-                        "{}: updated path to {}",
-                        abs_db_file.display(),
-                        candidate_path.display()
-                    );
-
-                    break;
+                if !pretend {
+                    storage::file::update_file(
+                        tx,
+                        &db_file.id,
+                        &scoped_candidate,
+                        db_file.fingerprint.clone(),
+                        mod_time_utc.into(),
+                        db_file.size,
+                        db_file.is_dir,
+                    )?;
                 }
+
+                println!(
+                    "{}: updated path to {}",
+                    abs_db_file.display(),
+                    candidate_path.display()
+                );
             }
         }
     }
@@ -376,6 +510,15 @@ fn repair_missing(
     Ok(())
 }
 
+fn report_duplicates(tx: &mut Transaction, base_path: &ScopedPath) -> Result<()> {
+    for group in crate::api::dupes::duplicate_groups(tx, Some(base_path))? {
+        let paths: Vec<_> = group.paths.iter().map(|p| p.display().to_string()).collect();
+        warn!("duplicate files: {}", paths.join(", "));
+    }
+
+    Ok(())
+}
+
 fn delete_untagged_files(tx: &mut Transaction, db_files: &[File]) -> Result<()> {
     info!("Purging untagged files");
 
@@ -423,6 +566,34 @@ fn rationalize_file_tags(tx: &mut Transaction, db_files: &[File]) -> Result<()>
     Ok(())
 }
 
+/// Return the stored fingerprint for a directory whose own mtime is unchanged and unambiguous,
+/// allowing the caller to skip the recursive content walk that computing a directory fingerprint
+/// otherwise requires. Returns `None` for non-directories or when the fingerprint must be
+/// recomputed. The same-second ambiguity rule from `determine_statuses` applies here too.
+fn cached_directory_fingerprint(
+    db_file: &File,
+    path: &ScopedPath,
+    status_start: DateTime<Utc>,
+) -> Result<Option<String>> {
+    if !db_file.is_dir {
+        return Ok(None);
+    }
+
+    let metadata = path.metadata()?;
+    let mod_time_utc: DateTime<Utc> = metadata.modified()?.into();
+
+    let is_ambiguous = status_start.signed_duration_since(mod_time_utc) < Duration::seconds(1);
+    if !is_ambiguous
+        && !db_file.mtime_ambiguous
+        && db_file.mod_time.timestamp() == mod_time_utc.timestamp()
+    {
+        debug!("{}: reusing cached directory fingerprint", path.display());
+        Ok(Some(db_file.fingerprint.clone()))
+    } else {
+        Ok(None)
+    }
+}
+
 struct Statuses<'a> {
     unmodified: Vec<&'a File>,
     modified: Vec<&'a File>,
@@ -432,6 +603,9 @@ struct Statuses<'a> {
 fn determine_statuses(db_files: &[File], root_path: Rc<CanonicalPath>) -> Result<Statuses> {
     info!("Determining file statuses");
 
+    // Captured once so every file is compared against the same notion of "now".
+    let status_start: DateTime<Utc> = Utc::now();
+
     let mut modified = vec![];
     let mut unmodified = vec![];
     let mut missing = vec![];
@@ -448,9 +622,21 @@ fn determine_statuses(db_files: &[File], root_path: Rc<CanonicalPath>) -> Result
         let metadata = abs_path.metadata()?;
         let mod_time = metadata.modified()?;
         let mod_time_utc: DateTime<Utc> = mod_time.into();
-        let mod_time_fixed: DateTime<FixedOffset> = mod_time_utc.into();
 
-        if db_file.size == metadata.len() && db_file.mod_time == mod_time_fixed {
+        // A file written within the same second it was fingerprinted could be modified again later
+        // in that second without its (second-granularity) mtime or size changing, so its stored
+        // fingerprint can no longer be trusted.
+        let is_ambiguous = status_start.signed_duration_since(mod_time_utc) < Duration::seconds(1);
+
+        // Compare mtimes truncated to whole seconds so that restores which lose sub-second
+        // precision don't flag every file as modified.
+        let size_matches = db_file.size == metadata.len();
+        let mtime_matches = db_file.mod_time.timestamp() == mod_time_utc.timestamp();
+
+        // A record flagged ambiguous when it was written can never be trusted on mtime alone, even
+        // in a later scan where the mtime no longer falls in the current second, so force it down
+        // the modified path until it is re-recorded at a strictly later second.
+        if size_matches && mtime_matches && !is_ambiguous && !db_file.mtime_ambiguous {
             debug!("{}: unmodified", abs_path.display());
             unmodified.push(db_file);
         } else {
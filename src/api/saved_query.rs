@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use crate::entities;
+use crate::errors::*;
+use crate::query::Expression;
+use crate::storage::{self, Storage};
+
+pub struct SavedQuery {
+    pub name: String,
+    pub query: String,
+}
+
+pub fn run_list_saved_queries(db_path: &Path) -> Result<Vec<SavedQuery>> {
+    let mut store = Storage::open(&db_path)?;
+    let mut tx = store.begin_transaction()?;
+
+    let queries = storage::saved_query::saved_queries(&mut tx)?;
+
+    tx.commit()?;
+
+    Ok(queries.into_iter().map(convert_to_output).collect())
+}
+
+/// Persist `query` under `name`, so it can later be referenced as `:name` from another query.
+/// Rejected if `query` doesn't parse, or if it would create a saved query that (directly or
+/// transitively) references itself.
+pub fn run_save_query(db_path: &Path, name: &str, query: &str) -> Result<()> {
+    let mut store = Storage::open(&db_path)?;
+    let mut tx = store.begin_transaction()?;
+
+    if let Some(expr) = Expression::parse(query)? {
+        expr.resolve_saved(
+            &mut |referenced| {
+                storage::saved_query::saved_query_by_name(&mut tx, referenced)
+                    .map(|saved| saved.map(|s| s.query))
+            },
+            &mut vec![name.to_owned()],
+        )?;
+    }
+
+    storage::saved_query::update_saved_query(&mut tx, name, query)?;
+
+    tx.commit()
+}
+
+pub fn run_delete_saved_query(db_path: &Path, name: &str) -> Result<()> {
+    let mut store = Storage::open(&db_path)?;
+    let mut tx = store.begin_transaction()?;
+
+    let deleted = storage::saved_query::delete_saved_query(&mut tx, name)?;
+    error_chain::ensure!(deleted > 0, "no such saved query '{}'", name);
+
+    tx.commit()
+}
+
+fn convert_to_output(saved: entities::SavedQuery) -> SavedQuery {
+    SavedQuery {
+        name: saved.name,
+        query: saved.query,
+    }
+}
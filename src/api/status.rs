@@ -1,14 +1,22 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
-use chrono::{DateTime, FixedOffset, Utc};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use rayon::prelude::*;
 
+use crate::entities::settings::Settings;
 use crate::entities::{File, FileSort};
 use crate::errors::*;
+use crate::fingerprint;
 use crate::path::{self, AbsPath, IntoAbsPath, ScopedPath};
+use crate::storage::status_cache::{self, CachedDirectory, DirCacheEntry};
 use crate::storage::{self, Storage};
 use crate::tree::Tree;
 
+/// The result of walking a subtree for new files: the `(path, status)` pairs found, plus any
+/// freshly observed directory listings to persist to the status cache.
+type WalkResult = (Vec<(AbsPath, PathStatus)>, Vec<(String, CachedDirectory)>);
+
 #[derive(Debug, PartialEq)]
 pub enum PathStatus {
     Missing,
@@ -48,18 +56,26 @@ impl Report {
     }
 }
 
-pub fn database_status(db_path: &Path, recursive: bool) -> Result<Report> {
+pub fn database_status(db_path: &Path, recursive: bool, use_cache: bool) -> Result<Report> {
     info!("Retrieving all files from database");
 
     let mut store = Storage::open(&db_path)?;
     let root_path = store.root_path.clone();
     let mut tx = store.begin_transaction()?;
 
+    let settings = storage::setting::settings(&mut tx)?;
     let db_files = storage::file::files(&mut tx, FileSort::Name)?;
 
     let mut report = Report::new();
 
-    check_files(&db_files, &root_path, &mut report)?;
+    // Captured once so every file in this run is compared against the same notion of "now": a
+    // file whose mtime falls within the same resolution window as `status_start` could have been
+    // written again after that mtime was observed, without the mtime itself changing.
+    let status_start: DateTime<Utc> = Utc::now();
+
+    for (path, status) in check_files(&db_files, &root_path, &settings, status_start)? {
+        report.add_entry(path, status);
+    }
 
     let mut tree = Tree::new();
     for db_file in db_files {
@@ -69,11 +85,36 @@ pub fn database_status(db_path: &Path, recursive: bool) -> Result<Report> {
         );
     }
 
+    let dir_cache = load_dir_cache(&mut tx, use_cache)?;
+
     let top_level_paths = tree.top_level().paths();
-    for path in top_level_paths {
-        find_new_files(AbsPath::from_unchecked(path), &mut report, recursive)?;
+    // Snapshot the paths known so far: each top-level subtree is walked on its own worker, so
+    // `find_new_files` can't consult (or mutate) `report` directly mid-walk.
+    let known_paths = report.paths.clone();
+    let walked: Vec<WalkResult> = top_level_paths
+        .into_par_iter()
+        .map(|path| {
+            find_new_files(
+                AbsPath::from_unchecked(path),
+                &known_paths,
+                recursive,
+                dir_cache.as_ref(),
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut to_cache = vec![];
+    for (new_entries, cached) in walked {
+        for (path, status) in new_entries {
+            if !report.contains_path(&path) {
+                report.add_entry(path, status);
+            }
+        }
+        to_cache.extend(cached);
     }
 
+    storage::status_cache::record_directories(&mut tx, &to_cache)?;
+
     tx.commit()?;
 
     Ok(report)
@@ -84,13 +125,21 @@ pub fn files_status(
     paths: &[PathBuf],
     recursive: bool,
     follow_symlinks: bool,
+    use_cache: bool,
 ) -> Result<Report> {
     let mut store = Storage::open(&db_path)?;
     let root_path = store.root_path.clone();
     let mut tx = store.begin_transaction()?;
 
+    let settings = storage::setting::settings(&mut tx)?;
     let mut report = Report::new();
 
+    // See the comment in `database_status`: one timestamp shared by every file checked in this run.
+    let status_start: DateTime<Utc> = Utc::now();
+
+    let dir_cache = load_dir_cache(&mut tx, use_cache)?;
+    let mut to_cache = vec![];
+
     for path in paths {
         let abs_path = AbsPath::from(&path, &root_path);
 
@@ -102,41 +151,89 @@ pub fn files_status(
         let scoped_path = ScopedPath::new(root_path.clone(), &resolved_path)?;
         let file_opt = storage::file::file_by_path(&mut tx, &scoped_path)?;
         if let Some(file) = file_opt {
-            check_file(&abs_path, &file, &mut report)?;
+            let status = check_file(&abs_path, &file, &settings, status_start)?;
+            report.add_entry(abs_path.clone(), status);
         }
 
         if recursive && (follow_symlinks || !is_symlink) {
             info!("{}: retrieving files from database", path.display());
 
             let db_files = storage::file::files_by_directory(&mut tx, &scoped_path)?;
-            check_files(&db_files, &root_path, &mut report)?;
+            for (entry_path, status) in check_files(&db_files, &root_path, &settings, status_start)? {
+                report.add_entry(entry_path, status);
+            }
         }
 
-        find_new_files(abs_path, &mut report, recursive)?;
+        let known_paths = report.paths.clone();
+        let (new_entries, cached) =
+            find_new_files(abs_path, &known_paths, recursive, dir_cache.as_ref())?;
+        for (entry_path, status) in new_entries {
+            if !report.contains_path(&entry_path) {
+                report.add_entry(entry_path, status);
+            }
+        }
+        to_cache.extend(cached);
     }
 
+    storage::status_cache::record_directories(&mut tx, &to_cache)?;
+
     tx.commit()?;
 
     Ok(report)
 }
 
-fn check_files(files: &[File], root_path: &AbsPath, report: &mut Report) -> Result<()> {
-    for file in files {
-        let abs_path = file.to_path_buf().into_abs_path(root_path);
-        check_file(&abs_path, file, report)?;
+/// Load the directory status cache, unless the caller passed `--no-cache` to force a full rescan.
+fn load_dir_cache(
+    tx: &mut storage::Transaction,
+    use_cache: bool,
+) -> Result<Option<HashMap<String, CachedDirectory>>> {
+    if use_cache {
+        Ok(Some(storage::status_cache::all_cached_directories(tx)?))
+    } else {
+        Ok(None)
     }
+}
 
-    Ok(())
+/// Stat (and, for ambiguous mtimes, fingerprint) every file in `files` across a worker pool, since
+/// on large databases this phase is dominated by syscall latency rather than CPU. Returns one
+/// `(path, status)` pair per file, in the same order as `files`, so the caller can merge them into
+/// a `Report` deterministically instead of needing `Report` itself to be thread-safe.
+fn check_files(
+    files: &[File],
+    root_path: &AbsPath,
+    settings: &Settings,
+    status_start: DateTime<Utc>,
+) -> Result<Vec<(AbsPath, PathStatus)>> {
+    files
+        .par_iter()
+        .map(|file| {
+            let abs_path = file.to_path_buf().into_abs_path(root_path);
+            let status = check_file(&abs_path, file, settings, status_start)?;
+            Ok((abs_path, status))
+        })
+        .collect()
 }
 
-fn check_file(abs_path: &AbsPath, file: &File, report: &mut Report) -> Result<()> {
+/// Decide `Missing`/`Modified`/`Tagged` for a single file. Size and mtime are checked first, since
+/// they're already available from a single `stat` call and settle the vast majority of files.
+///
+/// A size or mtime mismatch is conclusive: the file is `Modified`. A match is conclusive too,
+/// *unless* the mtime couldn't have distinguished a second write: either because the stored record
+/// itself was written in the same second it was fingerprinted (`file.mtime_ambiguous`), or because
+/// this status run started within that same second as the file's mtime (`status_start`). Only
+/// those genuinely ambiguous cases pay for a content fingerprint, compared against the one stored
+/// in the database to make the final call.
+fn check_file(
+    abs_path: &AbsPath,
+    file: &File,
+    settings: &Settings,
+    status_start: DateTime<Utc>,
+) -> Result<PathStatus> {
     info!("{}: checking file status", abs_path.display());
 
     if !abs_path.exists() {
         info!("{}: file is missing", abs_path.display());
-        report.add_entry(abs_path.clone(), PathStatus::Missing);
-
-        return Ok(());
+        return Ok(PathStatus::Missing);
     }
 
     let metadata = abs_path
@@ -148,41 +245,124 @@ fn check_file(abs_path: &AbsPath, file: &File, report: &mut Report) -> Result<()
 
     if metadata.len() != file.size || file.mod_time != mod_time_fixed {
         info!("{}: file is modified", abs_path.display());
-        report.add_entry(abs_path.clone(), PathStatus::Modified);
-    } else {
-        info!("{}: file is unchanged", abs_path.display());
-        report.add_entry(abs_path.clone(), PathStatus::Tagged);
+        return Ok(PathStatus::Modified);
+    }
+
+    let is_ambiguous = file.mtime_ambiguous
+        || status_start.signed_duration_since(mod_time_utc) < Duration::seconds(1);
+
+    if is_ambiguous {
+        debug!(
+            "{}: size and mtime match but mtime is ambiguous, fingerprinting",
+            abs_path.display()
+        );
+        let fingerprint = fingerprint::create(
+            abs_path,
+            &settings.file_fingerprint_algorithm()?,
+            &settings.directory_fingerprint_algorithm()?,
+            &settings.symlink_fingerprint_algorithm()?,
+        )?;
+
+        if fingerprint != file.fingerprint {
+            info!("{}: file is modified (fingerprint differs)", abs_path.display());
+            return Ok(PathStatus::Modified);
+        }
     }
 
-    Ok(())
+    info!("{}: file is unchanged", abs_path.display());
+    Ok(PathStatus::Tagged)
 }
 
-fn find_new_files(search_path: AbsPath, report: &mut Report, recursive: bool) -> Result<()> {
+/// Walk `search_path` (recursively, if `recursive`) for paths not already present in
+/// `known_paths`, reporting each as `Untagged`. Each directory's children are themselves walked in
+/// parallel, fanning out across the worker pool on deep or wide trees; results are still collected
+/// in the same sorted, depth-first order the original sequential walk produced, so output stays
+/// reproducible regardless of which worker finished first.
+///
+/// `dir_cache` holds, per directory, the entry listing and mtime observed by a previous run
+/// (`None` entirely if the caller passed `--no-cache`). When a directory's current mtime still
+/// matches the cached one, its `read_dir` is skipped and the cached listing is reused instead —
+/// only the (much cheaper) `stat` is paid on every run. Untagged/tagged status is always
+/// recomputed fresh against `known_paths` regardless of whether the listing came from the cache,
+/// so a stale cache entry can make the walk do unnecessary work but can never report wrong status.
+fn find_new_files(
+    search_path: AbsPath,
+    known_paths: &HashSet<AbsPath>,
+    recursive: bool,
+    dir_cache: Option<&HashMap<String, CachedDirectory>>,
+) -> Result<WalkResult> {
     info!("{}: finding new files", search_path.display());
 
-    if !report.contains_path(&search_path) {
-        report.add_entry(search_path.clone(), PathStatus::Untagged);
+    let mut found = vec![];
+    let mut to_cache = vec![];
+
+    if !known_paths.contains(&search_path) {
+        found.push((search_path.clone(), PathStatus::Untagged));
     }
 
     if recursive && search_path.is_dir() {
-        // Sort directory entries
-        let read_dir_iter = search_path.read_dir().map_err(|e| {
-            format!(
-                "{}: could not read directory listing: {}",
-                search_path.display(),
-                e
-            )
-        })?;
-        let mut entries = vec![];
-        for entry in read_dir_iter {
-            entries.push(entry?.path());
-        }
-        entries.sort();
-
-        for entry in entries {
-            find_new_files(AbsPath::from_unchecked(entry), report, recursive)?;
+        let cache_key = search_path.to_string_lossy().into_owned();
+        let mod_time: DateTime<Utc> = search_path
+            .metadata()
+            .map_err(|e| format!("{}: could not stat: {}", search_path.display(), e))?
+            .modified()?
+            .into();
+
+        let cached = dir_cache.and_then(|cache| cache.get(&cache_key));
+        let entries: Vec<(PathBuf, String, bool)> = match cached {
+            Some(cached) if cached.mtime == mod_time => cached
+                .children
+                .iter()
+                .map(|child| (search_path.join(&child.name), child.name.clone(), child.is_dir))
+                .collect(),
+            _ => {
+                let mut entries = vec![];
+                for entry in search_path.read_dir().map_err(|e| {
+                    format!(
+                        "{}: could not read directory listing: {}",
+                        search_path.display(),
+                        e
+                    )
+                })? {
+                    let entry = entry?;
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    let is_dir = entry.file_type()?.is_dir();
+                    entries.push((entry.path(), name, is_dir));
+                }
+                entries.sort();
+
+                let entry_hash =
+                    status_cache::hash_entry_names(entries.iter().map(|(_, name, _)| name.as_str()));
+                to_cache.push((
+                    cache_key,
+                    CachedDirectory {
+                        mtime: mod_time,
+                        entry_hash,
+                        children: entries
+                            .iter()
+                            .map(|(_, name, is_dir)| DirCacheEntry {
+                                name: name.clone(),
+                                is_dir: *is_dir,
+                            })
+                            .collect(),
+                    },
+                ));
+
+                entries
+            }
+        };
+
+        let children: Vec<WalkResult> = entries
+            .into_par_iter()
+            .map(|(path, _, _)| {
+                find_new_files(AbsPath::from_unchecked(path), known_paths, recursive, dir_cache)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for (child_found, child_to_cache) in children {
+            found.extend(child_found);
+            to_cache.extend(child_to_cache);
         }
     }
 
-    Ok(())
+    Ok((found, to_cache))
 }
@@ -1,6 +1,8 @@
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 
+use serde::Serialize;
+
 use crate::api;
 use crate::entities::{FileId, ValueId};
 use crate::errors::*;
@@ -8,19 +10,19 @@ use crate::path::{self, ScopedPath};
 use crate::storage::{self, Storage, Transaction};
 
 /// One group of tags. If the value name is present, then the tags correspond to it
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct ValueTagGroup {
     pub value_name: Option<String>,
     pub tag_names: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct FileTagGroup {
     pub path: PathBuf,
     pub tags: Vec<TagData>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct TagData {
     pub tag_name: String,
     pub value_name: Option<String>,
@@ -30,6 +32,12 @@ pub struct TagData {
 
 pub fn list_all_tags(db_path: &Path) -> Result<Vec<ValueTagGroup>> {
     let mut store = Storage::open(&db_path)?;
+    list_all_tags_with_store(&mut store)
+}
+
+/// Same as `list_all_tags`, but against a `Storage` the caller already has open, for callers (such
+/// as `cli::repl`) that hold a connection across several queries instead of reopening it each time.
+pub fn list_all_tags_with_store(store: &mut Storage) -> Result<Vec<ValueTagGroup>> {
     let mut tx = store.begin_transaction()?;
 
     info!("Retrieving all tags");
@@ -1,7 +1,10 @@
 use std::path::{Path, PathBuf};
 
+use chrono::{DateTime, Utc};
+
 use crate::errors::*;
 use crate::path::{self, AbsPath, ScopedPath};
+use crate::storage::status_cache::{self, CachedDirectory, DirCacheEntry};
 use crate::storage::{self, Storage};
 
 // Implementation note: instead of a callback, returning an iterator might be slightly more
@@ -18,6 +21,13 @@ pub fn list_untagged_for_paths(
     let root_path = store.root_path.clone();
     let mut tx = store.begin_transaction()?;
 
+    // Shared with `status`'s own recursive walk: a directory's own mtime only tells us its set of
+    // direct entries hasn't changed since this listing was cached, never whether those entries are
+    // currently tagged, so that's re-derived fresh against the database below regardless of
+    // whether the listing came from the cache or a fresh `read_dir`.
+    let dir_cache = storage::status_cache::all_cached_directories(&mut tx)?;
+    let mut to_cache: Vec<(String, CachedDirectory)> = vec![];
+
     // Clone the paths and store them in a stack
     // Contrarily to the Go implementation, this uses a stack instead of recursion. To keep similar
     // ordering of results, the iterator is reversed, both here and when adding items to the stack
@@ -38,17 +48,53 @@ pub fn list_untagged_for_paths(
         }
 
         if recursive && path.is_dir() {
+            let cache_key = path.to_string_lossy().into_owned();
+            let fs_mtime: DateTime<Utc> = path.metadata()?.modified()?.into();
+
+            let cached = dir_cache.get(&cache_key);
+            let mut entries: Vec<PathBuf> = match cached {
+                Some(cached) if cached.mtime == fs_mtime => cached
+                    .children
+                    .iter()
+                    .map(|child| path.join(&child.name))
+                    .collect(),
+                _ => {
+                    let mut entries = vec![];
+                    for entry in path.read_dir()? {
+                        entries.push(entry?.path());
+                    }
+                    entries.sort();
+
+                    let entry_hash = status_cache::hash_entry_names(entries.iter().filter_map(
+                        |p| p.file_name().and_then(|n| n.to_str()),
+                    ));
+                    to_cache.push((
+                        cache_key,
+                        CachedDirectory {
+                            mtime: fs_mtime,
+                            entry_hash,
+                            children: entries
+                                .iter()
+                                .map(|p| DirCacheEntry {
+                                    name: p.file_name().unwrap().to_string_lossy().into_owned(),
+                                    is_dir: p.is_dir(),
+                                })
+                                .collect(),
+                        },
+                    ));
+
+                    entries
+                }
+            };
+
             // Reverse the default order of directory entries
-            let mut entries = vec![];
-            for entry in path.read_dir()? {
-                entries.push(entry?.path());
-            }
             entries.reverse();
-
             paths.extend_from_slice(&entries);
         }
     }
 
+    storage::status_cache::record_directories(&mut tx, &to_cache)?;
+
     tx.commit()?;
 
     Ok(())
@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use crate::errors::*;
+use crate::storage::{self, Storage, Transaction};
+
+/// Reclaim storage left behind by bulk untagging: drop tags, values and files that are no longer
+/// referenced, then compact the database file.
+///
+/// The removals are performed in a single transaction so that a failure leaves the store
+/// untouched. The final VACUUM cannot run inside a transaction, so it is issued on the connection
+/// once the transaction has been committed.
+pub fn vacuum(db_path: &Path, pretend: bool) -> Result<()> {
+    let mut store = Storage::open(&db_path)?;
+
+    {
+        let mut tx = store.begin_transaction()?;
+        collect_garbage(&mut tx, pretend)?;
+        if !pretend {
+            tx.commit()?;
+        }
+    }
+
+    if !pretend {
+        info!("compacting the database");
+        store.vacuum()?;
+    }
+
+    Ok(())
+}
+
+fn collect_garbage(tx: &mut Transaction, pretend: bool) -> Result<()> {
+    // Counts are gathered up front so that the per-category totals can be reported even when
+    // running in pretend mode.
+    let tags = storage::tag::unused_tag_count(tx)?;
+    let values = storage::value::unused_value_count(tx)?;
+    let files = storage::file::untagged_file_count(tx)?;
+
+    if !pretend {
+        storage::tag::delete_unused_tags(tx)?;
+        storage::value::delete_unused_values(tx)?;
+        storage::file::delete_all_untagged_files(tx)?;
+    }
+
+    let verb = if pretend { "would remove" } else { "removed" };
+    info!("{} {} dangling tag(s)", verb, tags);
+    info!("{} {} dangling value(s)", verb, values);
+    info!("{} {} untagged file(s)", verb, files);
+
+    Ok(())
+}
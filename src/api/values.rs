@@ -1,14 +1,18 @@
 use std::path::Path;
 
+use serde::Serialize;
+
 use crate::api;
 use crate::errors::*;
 use crate::storage::{self, Storage, Transaction};
 
+#[derive(Debug, Serialize)]
 pub struct ValuesOutput {
     pub value_groups: Vec<ValueGroup>,
 }
 
 /// One group of values. If the tag name is present, then the values correspond to the tag.
+#[derive(Debug, Serialize)]
 pub struct ValueGroup {
     pub tag_name: Option<String>,
     pub value_names: Vec<String>,
@@ -1,4 +1,21 @@
+mod backup;
+mod config;
+mod copy;
+mod delete;
+mod dupes;
+mod files;
+mod hierarchy;
+mod imply;
 mod init;
+mod merge;
+mod rename;
+mod repair;
+mod repl;
+mod status;
+mod tags;
+mod untagged;
+mod vacuum;
+mod values;
 
 use std::path::PathBuf;
 use std::process;
@@ -35,6 +52,26 @@ pub struct GlobalOptions {
     /// Colorize the output (auto/always/never)
     #[structopt(long, default_value = "auto")]
     color: ColorMode,
+
+    /// Output format (text/json/jsonlines)
+    #[structopt(long, default_value = "text")]
+    pub format: Format,
+
+    /// The database is encrypted with SQLCipher
+    #[cfg(feature = "sqlcipher")]
+    #[structopt(long)]
+    encrypted: bool,
+
+    /// Passphrase for an encrypted database. If --encrypted is given and this is omitted, you
+    /// will be prompted for it interactively
+    #[cfg(feature = "sqlcipher")]
+    #[structopt(long, env = "TMSU_PASSPHRASE", hide_env_values = true)]
+    passphrase: Option<String>,
+
+    /// Log every SQL statement run against the database, along with a per-statement timing
+    /// summary once each transaction commits
+    #[structopt(long)]
+    trace_sql: bool,
 }
 
 arg_enum! {
@@ -46,20 +83,118 @@ arg_enum! {
     }
 }
 
+arg_enum! {
+    /// How structured command output should be rendered. `Text` is the human-readable default;
+    /// the JSON variants emit the listing APIs' output structs verbatim for scripting.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Format {
+        Text,
+        Json,
+        JsonLines,
+    }
+}
+
 #[derive(Debug, StructOpt)]
 enum SubCommands {
+    Backup(backup::BackupOptions),
+    Config(config::ConfigOptions),
+    Copy(copy::CopyOptions),
+    Delete(delete::DeleteOptions),
+    Dupes(dupes::DupesOptions),
+    Files(files::FilesOptions),
+    Hierarchy(hierarchy::HierarchyOptions),
+    Imply(imply::ImplyOptions),
     Init(init::InitOptions),
+    Merge(merge::MergeOptions),
+    Rename(rename::RenameOptions),
+    Repair(repair::RepairOptions),
+    Repl(repl::ReplOptions),
+    Restore(backup::RestoreOptions),
+    Status(status::StatusOptions),
+    Tags(tags::TagsOptions),
+    Untagged(untagged::UntaggedOptions),
+    Vacuum(vacuum::VacuumOptions),
+    Values(values::ValuesOptions),
 }
 
 /// CLI entry point, dispatching to subcommands
 pub fn run() -> Result<()> {
     let opt = TmsuOptions::from_args();
 
+    crate::storage::set_sql_tracing_enabled(opt.global_opts.trace_sql);
+
     match opt.cmd {
+        SubCommands::Backup(backup_opts) => backup_opts.execute(&opt.global_opts),
+        SubCommands::Config(config_opts) => config_opts.execute(&opt.global_opts),
+        SubCommands::Copy(copy_opts) => copy_opts.execute(&opt.global_opts),
+        SubCommands::Delete(delete_opts) => delete_opts.execute(&opt.global_opts),
+        SubCommands::Dupes(dupes_opts) => dupes_opts.execute(&opt.global_opts),
+        SubCommands::Files(files_opts) => files_opts.execute(&opt.global_opts),
+        SubCommands::Hierarchy(hierarchy_opts) => hierarchy_opts.execute(&opt.global_opts),
+        SubCommands::Imply(imply_opts) => imply_opts.execute(&opt.global_opts),
         SubCommands::Init(init_opts) => init_opts.execute(),
+        SubCommands::Merge(merge_opts) => merge_opts.execute(&opt.global_opts),
+        SubCommands::Rename(rename_opts) => rename_opts.execute(&opt.global_opts),
+        SubCommands::Repair(repair_opts) => repair_opts.execute(&opt.global_opts),
+        SubCommands::Repl(repl_opts) => repl_opts.execute(&opt.global_opts),
+        SubCommands::Restore(restore_opts) => restore_opts.execute(&opt.global_opts),
+        SubCommands::Status(status_opts) => status_opts.execute(&opt.global_opts),
+        SubCommands::Tags(tags_opts) => tags_opts.execute(&opt.global_opts),
+        SubCommands::Untagged(untagged_opts) => untagged_opts.execute(&opt.global_opts),
+        SubCommands::Vacuum(vacuum_opts) => vacuum_opts.execute(&opt.global_opts),
+        SubCommands::Values(values_opts) => values_opts.execute(&opt.global_opts),
     }
 }
 
+/// Render structured command output as JSON when a JSON format was requested. Returns `true` when
+/// the value was emitted, leaving the caller to fall back to its usual text rendering otherwise.
+pub fn emit_structured<T: serde::Serialize>(format: Format, value: &T) -> Result<bool> {
+    let json = match format {
+        Format::Text => return Ok(false),
+        Format::Json => serde_json::to_string_pretty(value),
+        Format::JsonLines => serde_json::to_string(value),
+    };
+    let json = json.map_err(|e| format!("could not serialize output: {}", e))?;
+    println!("{}", json);
+    Ok(true)
+}
+
+/// The passphrase to open an encrypted database with, per `global_opts`: `None` when
+/// `--encrypted` wasn't given, the explicit `--passphrase`/`TMSU_PASSPHRASE` value when one was,
+/// or an interactive prompt on stderr as a last resort.
+#[cfg(feature = "sqlcipher")]
+pub fn resolve_passphrase(global_opts: &GlobalOptions) -> Result<Option<String>> {
+    if !global_opts.encrypted {
+        return Ok(None);
+    }
+
+    if let Some(passphrase) = &global_opts.passphrase {
+        return Ok(Some(passphrase.clone()));
+    }
+
+    let passphrase = rpassword::prompt_password_stderr("passphrase: ")
+        .map_err(|e| format!("could not read passphrase: {}", e))?;
+    Ok(Some(passphrase))
+}
+
+/// Open the database at `db_path`, honoring `--encrypted`/`--passphrase` when the `sqlcipher`
+/// feature is enabled and transparently falling back to a plain `Storage::open` otherwise.
+#[cfg(feature = "sqlcipher")]
+pub fn open_store(db_path: &std::path::Path, global_opts: &GlobalOptions) -> Result<crate::storage::Storage> {
+    use crate::storage::Storage;
+
+    match resolve_passphrase(global_opts)? {
+        Some(passphrase) => Storage::open_encrypted(db_path, &passphrase),
+        None => Storage::open(db_path),
+    }
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+pub fn open_store(db_path: &std::path::Path, global_opts: &GlobalOptions) -> Result<crate::storage::Storage> {
+    let _ = global_opts;
+    crate::storage::Storage::open(db_path)
+}
+
 pub fn print_error(result: Result<()>) {
     if let Err(error) = result {
         eprintln!("tmsu: {}", error);
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use lazy_static::lazy_static;
+use structopt::StructOpt;
+
+use crate::api;
+use crate::cli::{locate_db, GlobalOptions};
+use crate::errors::*;
+
+lazy_static! {
+    static ref BACKUP_EXAMPLES: String =
+        super::generate_examples(&[("tmsu backup backup-2026-07-26.tmsu", None)]);
+    static ref RESTORE_EXAMPLES: String =
+        super::generate_examples(&[("tmsu restore backup-2026-07-26.tmsu", None)]);
+}
+
+/// Copies the database to FILE using SQLite's online backup API, so the copy is safe to take even
+/// while the database is open for writes elsewhere (unlike a plain file copy, which risks a torn
+/// snapshot).
+#[derive(Debug, StructOpt)]
+#[structopt(after_help(BACKUP_EXAMPLES.as_str()))]
+pub struct BackupOptions {
+    /// Path to write the backup to
+    #[structopt(parse(from_os_str))]
+    dest: PathBuf,
+}
+
+impl BackupOptions {
+    pub fn execute(&self, global_opts: &GlobalOptions) -> Result<()> {
+        let db_path = locate_db(&global_opts.database)?;
+        info!("Database path: {}", db_path.display());
+
+        api::backup::backup(&db_path, &self.dest, &mut print_progress)?;
+
+        Ok(())
+    }
+}
+
+/// Overwrites the database in place with the contents of FILE, using the same online backup
+/// mechanism as the backup subcommand, reversed.
+#[derive(Debug, StructOpt)]
+#[structopt(after_help(RESTORE_EXAMPLES.as_str()))]
+pub struct RestoreOptions {
+    /// Path to restore the backup from
+    #[structopt(parse(from_os_str))]
+    src: PathBuf,
+}
+
+impl RestoreOptions {
+    pub fn execute(&self, global_opts: &GlobalOptions) -> Result<()> {
+        let db_path = locate_db(&global_opts.database)?;
+        info!("Database path: {}", db_path.display());
+
+        api::backup::restore(&db_path, &self.src, &mut print_progress)?;
+
+        Ok(())
+    }
+}
+
+fn print_progress(remaining: i32, total: i32) {
+    eprint!("\rcopying pages... {}/{}", total - remaining, total);
+    if remaining == 0 {
+        eprintln!();
+    }
+}
@@ -13,6 +13,14 @@ use crate::errors::*;
 /// If a VALUE is specified then the setting is updated.
 #[derive(Debug, StructOpt)]
 pub struct ConfigOptions {
+    /// Resets a setting to its built-in default by removing it from the database
+    #[structopt(long, value_name("NAME"), conflicts_with("defaults"))]
+    reset: Option<String>,
+
+    /// Lists every recognized setting with its default value and type
+    #[structopt(long)]
+    defaults: bool,
+
     /// Config option name
     #[structopt(name = "setting")]
     settings: Vec<String>,
@@ -20,9 +28,17 @@ pub struct ConfigOptions {
 
 impl ConfigOptions {
     pub fn execute(&self, global_opts: &GlobalOptions) -> Result<()> {
+        if self.defaults {
+            return list_defaults();
+        }
+
         let db_path = locate_db(&global_opts.database)?;
         info!("Database path: {}", db_path.display());
 
+        if let Some(name) = &self.reset {
+            return api::config::run_config_reset_setting(&db_path, name);
+        }
+
         match self.settings.len() {
             0 => list_all_settings(&db_path)?,
             1 => process_param(&db_path, &self.settings[0], false)?,
@@ -45,6 +61,13 @@ fn list_all_settings(db_path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
+fn list_defaults() -> Result<()> {
+    for schema in api::config::run_config_list_defaults() {
+        println!("{}={} [{}]", schema.name, schema.default, schema.type_hint);
+    }
+    Ok(())
+}
+
 fn process_param(db_path: &PathBuf, setting_param: &str, print_with_name: bool) -> Result<()> {
     let parts: Vec<_> = setting_param.split('=').collect();
     match parts.len() {
@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use lazy_static::lazy_static;
+use structopt::StructOpt;
+
+use crate::api;
+use crate::cli::{locate_db, GlobalOptions};
+use crate::errors::*;
+
+lazy_static! {
+    static ref EXAMPLES: String = super::generate_examples(&[
+        ("tmsu dupes", None),
+        ("tmsu dupes --path=/home/sally # restrict to a subtree", None),
+    ]);
+}
+
+/// Identifies sets of files that share the same content by grouping them on their stored
+/// fingerprint.
+///
+/// Since fingerprints are recorded when files are tagged, this reports duplicates without a second
+/// hashing pass. Only clusters of two or more files with a non-empty fingerprint are shown.
+#[derive(Debug, StructOpt)]
+#[structopt(after_help(EXAMPLES.as_str()))]
+pub struct DupesOptions {
+    /// Limit the search to files in the database under PATH
+    #[structopt(name("path"), short, long)]
+    base_path: Option<PathBuf>,
+}
+
+impl DupesOptions {
+    pub fn execute(&self, global_opts: &GlobalOptions) -> Result<()> {
+        let db_path = locate_db(&global_opts.database)?;
+        info!("Database path: {}", db_path.display());
+
+        let groups = api::dupes::run_dupes(&db_path, &self.base_path)?;
+
+        if super::emit_structured(global_opts.format, &groups)? {
+            return Ok(());
+        }
+
+        for (idx, group) in groups.iter().enumerate() {
+            if idx > 0 {
+                println!();
+            }
+            println!("Set of {} duplicates:", group.paths.len());
+            for path in &group.paths {
+                println!("  {}", path.display());
+            }
+        }
+
+        Ok(())
+    }
+}
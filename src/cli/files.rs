@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
 use lazy_static::lazy_static;
+use serde::Serialize;
 use structopt::clap::arg_enum;
 use structopt::StructOpt;
 
@@ -9,6 +10,13 @@ use crate::cli::{locate_db, GlobalOptions};
 use crate::entities::FileSort;
 use crate::errors::*;
 
+/// Structured stand-in for `--count`'s output when `--format` asks for JSON, so the count arrives
+/// as a named field rather than as a bare number.
+#[derive(Serialize)]
+struct FileCount {
+    count: usize,
+}
+
 lazy_static! {
     static ref EXAMPLES: String = super::generate_examples(&[
         (
@@ -83,6 +91,10 @@ pub struct FilesOptions {
     #[structopt(short, long)]
     ignore_case: bool,
 
+    /// Expands each queried tag to match files tagged with any of its containment descendants
+    #[structopt(long)]
+    hierarchy: bool,
+
     /// Delimits files with a NUL character rather than newline
     #[structopt(short("0"), long)]
     print0: bool,
@@ -122,6 +134,7 @@ impl FilesOptions {
             &str_query,
             self.explicit_only,
             self.ignore_case,
+            self.hierarchy,
             self.base_path.as_deref(),
             self.sort.as_ref().map(convert_sort_mode),
         )?;
@@ -135,8 +148,16 @@ impl FilesOptions {
 
         // Print matches
         if self.show_count {
-            println!("{}", filtered_files.len());
+            let count = filtered_files.len();
+            if super::emit_structured(global_opts.format, &FileCount { count })? {
+                return Ok(());
+            }
+            println!("{}", count);
         } else {
+            if super::emit_structured(global_opts.format, &filtered_files)? {
+                return Ok(());
+            }
+
             let cwd = super::getcwd()?;
             for file_data in filtered_files {
                 let rel_path = super::rel_to(&file_data.path, &cwd);
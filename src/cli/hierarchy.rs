@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use ansi_term::Colour;
+use lazy_static::lazy_static;
+use structopt::StructOpt;
+
+use crate::api;
+use crate::cli::{locate_db, GlobalOptions};
+use crate::errors::*;
+
+lazy_static! {
+    static ref EXAMPLES: String = super::generate_examples(&[
+        ("tmsu hierarchy location europe", None),
+        ("tmsu hierarchy europe france germany", None),
+        ("tmsu hierarchy", Some("location -> europe")),
+        ("tmsu hierarchy --delete location europe", None),
+    ]);
+}
+
+/// Defines a containment relationship such that the parent TAG contains each CHILD tag.
+///
+/// Unlike implications, containment confers no inheritance: it groups tags into a hierarchy purely
+/// for organization and roll-up queries. Querying a parent tag with the files subcommand's
+/// --hierarchy flag matches any file tagged with a transitive descendant.
+///
+/// When run without arguments lists the set of containment relationships.
+#[derive(Debug, StructOpt)]
+#[structopt(after_help(EXAMPLES.as_str()))]
+pub struct HierarchyOptions {
+    /// Deletes the containment relationship
+    #[structopt(short, long, requires_all(&["parent", "child"]))]
+    delete: bool,
+
+    /// Parent tag that contains the children
+    #[structopt(requires("child"))]
+    parent: Option<String>,
+
+    /// Child tag(s) contained by the parent
+    #[structopt(requires("parent"))]
+    child: Vec<String>,
+}
+
+impl HierarchyOptions {
+    pub fn execute(&self, global_opts: &GlobalOptions) -> Result<()> {
+        let db_path = locate_db(&global_opts.database)?;
+        info!("Database path: {}", db_path.display());
+
+        let use_colors = super::should_use_colour(&global_opts.color);
+
+        match &self.parent {
+            None => list_hierarchies(&db_path, use_colors),
+            Some(parent) => {
+                let containments = create_api_containments(parent, &self.child);
+                if self.delete {
+                    api::hierarchy::delete_hierarchies(&db_path, &containments)
+                } else {
+                    api::hierarchy::add_hierarchies(&db_path, &containments)
+                }
+            }
+        }
+    }
+}
+
+fn list_hierarchies(db_path: &PathBuf, use_colors: bool) -> Result<()> {
+    info!("Retrieving tag containments");
+
+    let output = api::hierarchy::run_hierarchy_list(db_path)?;
+
+    let max_parent_width = output
+        .containments
+        .iter()
+        .map(|c| c.parent.len())
+        .max()
+        .unwrap_or_default();
+
+    for containment in output.containments {
+        let mut child = containment.child;
+        if use_colors {
+            child = Colour::Cyan.paint(child).to_string();
+        }
+        println!(
+            "{:>width$} -> {}",
+            &containment.parent,
+            &child,
+            width = max_parent_width
+        );
+    }
+
+    Ok(())
+}
+
+fn create_api_containments(parent: &str, children: &[String]) -> Vec<api::hierarchy::Containment> {
+    children
+        .iter()
+        .map(|child| api::hierarchy::Containment {
+            parent: parent.to_owned(),
+            child: child.to_owned(),
+        })
+        .collect()
+}
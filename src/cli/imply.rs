@@ -33,10 +33,27 @@ pub struct ImplyOptions {
     #[structopt(short, long, requires_all(&["tag", "implied"]))]
     delete: bool,
 
+    /// Prints the full set of tags implied by TAG, indented by depth
+    #[structopt(long, requires("tag"), conflicts_with("delete"))]
+    tree: bool,
+
+    /// Imports implication rules from FILE (one `implying -> implied` per line)
+    #[structopt(long, value_name("FILE"), parse(from_os_str))]
+    import: Option<PathBuf>,
+
+    /// Exports all single-antecedent implication rules to FILE (`--and` rules are not exported)
+    #[structopt(long, value_name("FILE"), parse(from_os_str))]
+    export: Option<PathBuf>,
+
     /// Source tag for the implication
     #[structopt(requires("implied"))]
     tag: Option<TagAndValueNames>,
 
+    /// Additional tag(s) that must ALSO be present for the implication to apply (a conjunctive
+    /// antecedent), e.g. `tmsu imply a c --and b=2` means "a AND b=2" implies "c"
+    #[structopt(long, requires("tag"), conflicts_with("delete"))]
+    and: Vec<TagAndValueNames>,
+
     /// Target tag(s) for the implication
     #[structopt(requires("tag"))]
     implied: Vec<TagAndValueNames>,
@@ -49,13 +66,24 @@ impl ImplyOptions {
 
         let use_colors = super::should_use_colour(&global_opts.color);
 
+        if let Some(path) = &self.import {
+            return api::imply::import_implications(&db_path, path);
+        }
+        if let Some(path) = &self.export {
+            return api::imply::export_implications(&db_path, path);
+        }
+
         match &self.tag {
             None => list_implications(&db_path, use_colors),
             Some(src_tag) => {
-                if self.delete {
+                if self.tree {
+                    print_implication_tree(&db_path, src_tag)
+                } else if self.delete {
                     delete_implications(&db_path, src_tag, &self.implied)
-                } else {
+                } else if self.and.is_empty() {
                     add_implications(&db_path, &src_tag, &self.implied)
+                } else {
+                    add_compound_implications(&db_path, src_tag, &self.and, &self.implied)
                 }
             }
         }
@@ -111,6 +139,21 @@ fn format_tag_value(tag_name: &str, value_name: &Option<String>) -> String {
     }
 }
 
+fn print_implication_tree(db_path: &PathBuf, src: &TagAndValueNames) -> Result<()> {
+    let src = api::imply::TagAndOptionalValue {
+        tag_name: src.tag_name.clone(),
+        value_name: src.value_name.clone(),
+    };
+
+    info!("Walking implication tree for '{}'", src);
+    println!("{}", src);
+    for (depth, node) in api::imply::run_imply_tree(db_path, &src)? {
+        println!("{}{}", "  ".repeat(depth), node);
+    }
+
+    Ok(())
+}
+
 fn delete_implications(
     db_path: &PathBuf,
     src_tag_and_val: &TagAndValueNames,
@@ -129,6 +172,35 @@ fn add_implications(
     api::imply::add_implications(db_path, &implications)
 }
 
+fn add_compound_implications(
+    db_path: &PathBuf,
+    src_tag_and_val: &TagAndValueNames,
+    and_tags: &[TagAndValueNames],
+    implied: &[TagAndValueNames],
+) -> Result<()> {
+    let antecedents = create_api_tag_and_values(std::iter::once(src_tag_and_val).chain(and_tags));
+
+    for tgt in implied {
+        let implied = api::imply::TagAndOptionalValue {
+            tag_name: tgt.tag_name.clone(),
+            value_name: tgt.value_name.clone(),
+        };
+        api::imply::add_compound_implication(db_path, &antecedents, &implied)?;
+    }
+
+    Ok(())
+}
+
+fn create_api_tag_and_values<'a>(
+    tags: impl Iterator<Item = &'a TagAndValueNames>,
+) -> Vec<api::imply::TagAndOptionalValue> {
+    tags.map(|t| api::imply::TagAndOptionalValue {
+        tag_name: t.tag_name.clone(),
+        value_name: t.value_name.clone(),
+    })
+    .collect()
+}
+
 fn create_api_implications(
     implying: &TagAndValueNames,
     implied: &[TagAndValueNames],
@@ -16,7 +16,15 @@ lazy_static! {
             None
         ),
         (
-            "tmsu repair --manual /home/bob /home/fred # manually repair paths",
+            "tmsu repair --manual /home/bob=/home/fred # relocate a moved root",
+            None
+        ),
+        (
+            "tmsu repair --manual /a=/b /c=/d # relocate several roots at once",
+            None
+        ),
+        (
+            "tmsu repair --manual --regex '^/old/(.*)=/new/$1' # regex relocation",
             None
         ),
     ]);
@@ -34,9 +42,12 @@ lazy_static! {
 ///
 /// Files that have been both moved and modified cannot be repaired and must be manually relocated.
 ///
-/// When run with the --manual option, any paths that begin with OLD are updated to begin with NEW.
-/// Any affected files' fingerprints are updated providing the file exists at the new location. No
-/// further repairs are attempted in this mode.
+/// When run with the --manual option, the remaining arguments are OLD=NEW mappings and any stored
+/// path beginning with OLD is updated to begin with NEW. Several mappings may be given and are
+/// applied longest-OLD-first. With --regex, OLD is a regular expression matched against the whole
+/// stored path and NEW is a replacement template supporting capturing groups ($1, $2, ...), with
+/// the first matching mapping winning. Any affected files' fingerprints are updated providing the
+/// file exists at the new location. No further repairs are attempted in this mode.
 #[derive(Debug, StructOpt)]
 #[structopt(after_help(EXAMPLES.as_str()))]
 pub struct RepairOptions {
@@ -64,6 +75,10 @@ pub struct RepairOptions {
     #[structopt(short, long, conflicts_with_all(&["remove", "rationalize", "unmodified"]))]
     manual: bool,
 
+    /// Treat each OLD in an OLD=NEW mapping as a regular expression (requires --manual)
+    #[structopt(long, requires("manual"))]
+    regex: bool,
+
     /// File paths
     #[structopt(conflicts_with("values"))]
     paths: Vec<PathBuf>,
@@ -75,11 +90,8 @@ impl RepairOptions {
         info!("Database path: {}", db_path.display());
 
         if self.manual {
-            if self.paths.len() != 2 {
-                return Err("Expected two arguments for the --manual option".into());
-            }
-
-            api::repair::manual_repair(&db_path, &self.paths[0], &self.paths[1], self.pretend)?;
+            let mappings = parse_mappings(&self.paths)?;
+            api::repair::manual_repair(&db_path, &mappings, self.regex, self.pretend)?;
         } else {
             api::repair::full_repair(
                 &db_path,
@@ -95,3 +107,26 @@ impl RepairOptions {
         Ok(())
     }
 }
+
+/// Parse the positional arguments of a manual repair into `(OLD, NEW)` mappings, each given as
+/// `OLD=NEW`. At least one mapping is required.
+fn parse_mappings(paths: &[PathBuf]) -> Result<Vec<(String, String)>> {
+    if paths.is_empty() {
+        return Err("Expected at least one OLD=NEW mapping for the --manual option".into());
+    }
+
+    let mut mappings = Vec::with_capacity(paths.len());
+    for path in paths {
+        let arg = path
+            .to_str()
+            .ok_or_else(|| format!("mapping '{}' is not valid UTF-8", path.display()))?;
+        match arg.splitn(2, '=').collect::<Vec<_>>()[..] {
+            [old, new] => mappings.push((old.to_owned(), new.to_owned())),
+            _ => {
+                return Err(format!("expected an OLD=NEW mapping, got '{}'", arg).into());
+            }
+        }
+    }
+
+    Ok(mappings)
+}
@@ -0,0 +1,147 @@
+use std::path::PathBuf;
+
+use rustyline::error::ReadlineError;
+use rustyline::Editor;
+use structopt::StructOpt;
+
+use crate::api;
+use crate::cli::{locate_db, open_store, GlobalOptions};
+use crate::errors::*;
+use crate::storage::Storage;
+
+/// Opens the database once, then runs an interactive read-eval-print loop against the same
+/// connection, instead of reopening it for every command the way the other subcommands do.
+///
+/// Supported commands: `files QUERY`, `tags`, `imply`, `config`, `help`, `quit`/`exit`. Command
+/// history is kept across lines (navigate with the up/down arrow keys) and persisted alongside
+/// the database so it survives between sessions.
+#[derive(Debug, StructOpt)]
+pub struct ReplOptions {}
+
+impl ReplOptions {
+    pub fn execute(&self, global_opts: &GlobalOptions) -> Result<()> {
+        let db_path = locate_db(&global_opts.database)?;
+        info!("Database path: {}", db_path.display());
+
+        let mut store = open_store(&db_path, global_opts)?;
+
+        let mut rl = Editor::<()>::new();
+        let history_path = history_file_path(&db_path);
+        let _ = rl.load_history(&history_path);
+
+        println!(
+            "tmsu: connected to {}. Type 'help' for a list of commands.",
+            db_path.display()
+        );
+
+        loop {
+            match rl.readline("tmsu> ") {
+                Ok(line) => {
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    rl.add_history_entry(line);
+
+                    if line == "quit" || line == "exit" {
+                        break;
+                    }
+
+                    if let Err(err) = dispatch(&mut store, line) {
+                        eprintln!("tmsu: {}", err);
+                    }
+                }
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+                Err(err) => {
+                    eprintln!("tmsu: {}", err);
+                    break;
+                }
+            }
+        }
+
+        let _ = rl.save_history(&history_path);
+
+        Ok(())
+    }
+}
+
+/// Keep history next to the database itself, so a repeated `tmsu repl` on the same database picks
+/// up where the last session left off.
+fn history_file_path(db_path: &PathBuf) -> PathBuf {
+    db_path.with_extension("repl_history")
+}
+
+fn dispatch(store: &mut Storage, line: &str) -> Result<()> {
+    let (command, rest) = match line.find(char::is_whitespace) {
+        Some(idx) => (&line[..idx], line[idx..].trim()),
+        None => (line, ""),
+    };
+
+    match command {
+        "files" => run_files(store, rest),
+        "tags" => run_tags(store),
+        "imply" => run_imply(store),
+        "config" => run_config(store),
+        "help" => {
+            print_help();
+            Ok(())
+        }
+        _ => Err(format!(
+            "unknown command '{}' (type 'help' for a list of commands)",
+            command
+        )
+        .into()),
+    }
+}
+
+fn run_files(store: &mut Storage, str_query: &str) -> Result<()> {
+    let files =
+        api::files::list_matching_with_store(store, str_query, false, false, false, None, None)?;
+
+    let cwd = super::getcwd()?;
+    for file_data in files {
+        let rel_path = super::rel_to(&file_data.path, &cwd);
+        println!("{}", rel_path.display());
+    }
+
+    Ok(())
+}
+
+fn run_tags(store: &mut Storage) -> Result<()> {
+    let groups = api::tags::list_all_tags_with_store(store)?;
+    for group in groups {
+        for tag_name in group.tag_names {
+            println!("{}", tag_name);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_imply(store: &mut Storage) -> Result<()> {
+    let output = api::imply::run_imply_list_with_store(store)?;
+    for implication in output.implications {
+        println!("{} -> {}", implication.implying, implication.implied);
+    }
+
+    Ok(())
+}
+
+fn run_config(store: &mut Storage) -> Result<()> {
+    let settings = api::config::run_config_list_all_settings_with_store(store)?;
+    for setting in settings {
+        println!("{} = {}", setting.name, setting.value);
+    }
+
+    Ok(())
+}
+
+fn print_help() {
+    println!("Available commands:");
+    println!("  files QUERY   list files matching QUERY");
+    println!("  tags          list all known tags");
+    println!("  imply         list tag implications");
+    println!("  config        list configuration settings");
+    println!("  help          show this message");
+    println!("  quit, exit    leave the REPL");
+}
@@ -42,6 +42,10 @@ pub struct StatusOptions {
     #[structopt(short("P"), long)]
     no_dereference: bool,
 
+    /// Do not use the cached directory listings from a previous run (forces a full re-scan)
+    #[structopt(long)]
+    no_cache: bool,
+
     /// File paths
     #[structopt()]
     paths: Vec<PathBuf>,
@@ -52,14 +56,16 @@ impl StatusOptions {
         let db_path = locate_db(&global_opts.database)?;
         info!("Database path: {}", db_path.display());
 
+        let use_cache = !self.no_cache;
         let report = if self.paths.is_empty() {
-            api::status::database_status(&db_path, !self.directory_only)?
+            api::status::database_status(&db_path, !self.directory_only, use_cache)?
         } else {
             api::status::files_status(
                 &db_path,
                 &self.paths,
                 !self.directory_only,
                 !self.no_dereference,
+                use_cache,
             )?
         };
 
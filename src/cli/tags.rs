@@ -3,6 +3,7 @@ use std::path::{Path, PathBuf};
 
 use ansi_term::{Colour, Style};
 use lazy_static::lazy_static;
+use serde::Serialize;
 use structopt::clap::arg_enum;
 use structopt::StructOpt;
 
@@ -10,6 +11,21 @@ use crate::api;
 use crate::cli::{extract_names, locate_db, print_columns, GlobalOptions, TagOrValueName};
 use crate::errors::*;
 
+/// Structured stand-in for `--count` against a value/"all tags" grouping: the tag names themselves
+/// are dropped once `--count` is given, so only the count is serialized.
+#[derive(Serialize)]
+struct ValueTagCount {
+    value_name: Option<String>,
+    count: usize,
+}
+
+/// Structured stand-in for `--count` against a file grouping.
+#[derive(Serialize)]
+struct FileTagCount {
+    path: PathBuf,
+    count: usize,
+}
+
 lazy_static! {
     static ref EXAMPLES: String = super::generate_examples(&[
         ("tmsu tags", Some("mp3 music opera")),
@@ -83,6 +99,9 @@ impl TagsOptions {
         if !self.value_names.is_empty() {
             let value_names = extract_names(&self.value_names);
             let tag_groups = api::tags::list_tags_for_values(&db_path, &value_names)?;
+            if self.emit_value_tag_groups(global_opts, &tag_groups)? {
+                return Ok(());
+            }
             print_value_tag_groups(
                 &tag_groups,
                 &self.name_mode,
@@ -97,6 +116,21 @@ impl TagsOptions {
                 !self.no_dereference,
                 self.explicit,
             )?;
+            let emitted = if self.show_count {
+                let counts: Vec<FileTagCount> = tag_groups
+                    .iter()
+                    .map(|g| FileTagCount {
+                        path: g.path.clone(),
+                        count: g.tags.len(),
+                    })
+                    .collect();
+                super::emit_structured(global_opts.format, &counts)?
+            } else {
+                super::emit_structured(global_opts.format, &tag_groups)?
+            };
+            if emitted {
+                return Ok(());
+            }
             print_file_tag_groups(
                 &self.paths,
                 &tag_groups,
@@ -107,6 +141,9 @@ impl TagsOptions {
             )?;
         } else {
             let tag_groups = api::tags::list_all_tags(&db_path)?;
+            if self.emit_value_tag_groups(global_opts, &tag_groups)? {
+                return Ok(());
+            }
             print_value_tag_groups(
                 &tag_groups,
                 &self.name_mode,
@@ -118,6 +155,27 @@ impl TagsOptions {
 
         Ok(())
     }
+
+    /// Emit `groups` as structured output, substituting tag counts for tag names when `--count`
+    /// was given. Returns `true` when something was emitted (i.e. `--format` wasn't `text`).
+    fn emit_value_tag_groups(
+        &self,
+        global_opts: &GlobalOptions,
+        groups: &[api::tags::ValueTagGroup],
+    ) -> Result<bool> {
+        if self.show_count {
+            let counts: Vec<ValueTagCount> = groups
+                .iter()
+                .map(|g| ValueTagCount {
+                    value_name: g.value_name.clone(),
+                    count: g.tag_names.len(),
+                })
+                .collect();
+            super::emit_structured(global_opts.format, &counts)
+        } else {
+            super::emit_structured(global_opts.format, groups)
+        }
+    }
 }
 
 fn print_value_tag_groups(
@@ -0,0 +1,38 @@
+use lazy_static::lazy_static;
+use structopt::StructOpt;
+
+use crate::api;
+use crate::cli::{locate_db, GlobalOptions};
+use crate::errors::*;
+
+lazy_static! {
+    static ref EXAMPLES: String = super::generate_examples(&[
+        ("tmsu vacuum", None),
+        ("tmsu vacuum --pretend # preview what would be removed", None),
+    ]);
+}
+
+/// Reclaims storage occupied by dangling metadata and compacts the database.
+///
+/// Tags and values that are no longer referenced by any tagging, together with files that have
+/// lost all of their taggings, are removed before the database file is rebuilt to release the
+/// space they occupied. This is a heavier operation than `repair`, which only corrects paths and
+/// fingerprints, and is worth running after bulk untagging.
+#[derive(Debug, StructOpt)]
+#[structopt(after_help(EXAMPLES.as_str()))]
+pub struct VacuumOptions {
+    /// Do not make any changes
+    #[structopt(short("P"), long)]
+    pretend: bool,
+}
+
+impl VacuumOptions {
+    pub fn execute(&self, global_opts: &GlobalOptions) -> Result<()> {
+        let db_path = locate_db(&global_opts.database)?;
+        info!("Database path: {}", db_path.display());
+
+        api::vacuum::vacuum(&db_path, self.pretend)?;
+
+        Ok(())
+    }
+}
@@ -39,6 +39,10 @@ impl ValuesOptions {
 
         let values_output = api::values::run_values(&db_path, &names)?;
 
+        if super::emit_structured(global_opts.format, &values_output)? {
+            return Ok(());
+        }
+
         match values_output.value_groups.len() {
             // When there is only one group, it means either that no tag was requested or that one
             // tag was requested. In either case, we don't print the tag name.
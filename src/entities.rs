@@ -121,6 +121,17 @@ impl fmt::Display for FileId {
     }
 }
 
+/// Identifies a compound implication rule (one with one or more conjunctive antecedents), i.e. a
+/// row in the `implication_rule` table. See `CompoundImplication`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImplicationRuleId(pub u32);
+
+impl fmt::Display for ImplicationRuleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub struct Tag {
     pub id: TagId,
@@ -170,10 +181,29 @@ pub enum FileFingerprintAlgorithm {
     DynamicSha256,
     DynamicMd5,
     DynamicBlake2b,
+    DynamicBlake3,
     RegularSha1,
     RegularSha256,
     RegularMd5,
     RegularBlake2b,
+    RegularBlake3,
+}
+
+impl FileFingerprintAlgorithm {
+    /// The setting strings accepted by `from_str`, in the order they are documented.
+    pub const SUPPORTED: &'static [&'static str] = &[
+        "none",
+        "dynamic:MD5",
+        "dynamic:SHA1",
+        "dynamic:SHA256",
+        "dynamic:BLAKE2b",
+        "dynamic:BLAKE3",
+        "MD5",
+        "SHA1",
+        "SHA256",
+        "BLAKE2b",
+        "BLAKE3",
+    ];
 }
 
 impl FromStr for FileFingerprintAlgorithm {
@@ -186,11 +216,18 @@ impl FromStr for FileFingerprintAlgorithm {
             "dynamic:SHA1" => Ok(FileFingerprintAlgorithm::DynamicSha1),
             "dynamic:SHA256" => Ok(FileFingerprintAlgorithm::DynamicSha256),
             "dynamic:BLAKE2b" => Ok(FileFingerprintAlgorithm::DynamicBlake2b),
+            "dynamic:BLAKE3" => Ok(FileFingerprintAlgorithm::DynamicBlake3),
             "MD5" => Ok(FileFingerprintAlgorithm::RegularMd5),
             "SHA1" => Ok(FileFingerprintAlgorithm::RegularSha1),
             "SHA256" => Ok(FileFingerprintAlgorithm::RegularSha256),
             "BLAKE2b" => Ok(FileFingerprintAlgorithm::RegularBlake2b),
-            _ => Err(format!("unsupported symbolic link fingerprint algorithm '{}'", s).into()),
+            "BLAKE3" => Ok(FileFingerprintAlgorithm::RegularBlake3),
+            _ => Err(format!(
+                "unsupported file fingerprint algorithm '{}' (expected one of: {})",
+                s,
+                Self::SUPPORTED.join(", ")
+            )
+            .into()),
         }
     }
 }
@@ -200,6 +237,22 @@ pub enum DirectoryFingerprintAlgorithm {
     None,
     DynamicSumSizes,
     RegularSumSizes,
+    /// Fold each descendant's `(relative-path, fingerprint)` pair, in sorted order, into a single
+    /// parent hash so that a directory's fingerprint changes iff any descendant's content or
+    /// layout changes. See `fingerprint::create` for the folding rule.
+    DynamicRecursive,
+    RegularRecursive,
+}
+
+impl DirectoryFingerprintAlgorithm {
+    /// The setting strings accepted by `from_str`, in the order they are documented.
+    pub const SUPPORTED: &'static [&'static str] = &[
+        "none",
+        "sumSizes",
+        "dynamic:sumSizes",
+        "recursive",
+        "dynamic:recursive",
+    ];
 }
 
 impl FromStr for DirectoryFingerprintAlgorithm {
@@ -210,7 +263,14 @@ impl FromStr for DirectoryFingerprintAlgorithm {
             "none" => Ok(DirectoryFingerprintAlgorithm::None),
             "sumSizes" => Ok(DirectoryFingerprintAlgorithm::RegularSumSizes),
             "dynamic:sumSizes" => Ok(DirectoryFingerprintAlgorithm::DynamicSumSizes),
-            _ => Err(format!("unsupported directory fingerprint algorithm '{}'", s).into()),
+            "recursive" => Ok(DirectoryFingerprintAlgorithm::RegularRecursive),
+            "dynamic:recursive" => Ok(DirectoryFingerprintAlgorithm::DynamicRecursive),
+            _ => Err(format!(
+                "unsupported directory fingerprint algorithm '{}' (expected one of: {})",
+                s,
+                Self::SUPPORTED.join(", ")
+            )
+            .into()),
         }
     }
 }
@@ -245,6 +305,12 @@ pub struct File {
     pub mod_time: DateTime<FixedOffset>,
     pub size: u64,
     pub is_dir: bool,
+    /// Marks a record whose mtime fell in the same wall-clock second as the moment it was written.
+    /// On filesystems with coarse mtime granularity a later edit within that same second can leave
+    /// the visible mtime unchanged, so status and repair never trust a flagged record on mtime
+    /// alone and always re-fingerprint it. No column of the `file` table stores the flag and
+    /// `storage::file` reads every record as `false`, so no loaded record is flagged.
+    pub mtime_ambiguous: bool,
 }
 
 impl File {
@@ -284,12 +350,49 @@ pub struct Implication {
     pub implied_value: Option<Value>,
 }
 
-#[derive(Debug, Clone)]
+/// A containment edge in the tag hierarchy: `parent` contains `child`. Unlike an `Implication`,
+/// this carries no value and confers no inheritance — it exists purely to organize tags into
+/// roll-up groups (e.g. `location` HAS `europe` HAS `france`).
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct TagHierarchy {
+    pub parent: Tag,
+    pub child: Tag,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TagIdValueIdPair {
     pub tag_id: TagId,
     pub value_id: OptionalValueId,
 }
 
+/// A forward-chaining implication rule: a file bearing every pair in `antecedents` implicitly
+/// gains `implied`. The common single-antecedent case (`a => b`, stored in the `implication`
+/// table) has exactly one antecedent; `antecedents.len() > 1` represents a conjunctive rule
+/// (`a AND b=2 => c`, stored in `implication_rule`/`implication_rule_antecedent`).
+#[derive(Debug, Clone)]
+pub struct CompoundImplication {
+    pub id: ImplicationRuleId,
+    pub antecedents: Vec<TagIdValueIdPair>,
+    pub implied: TagIdValueIdPair,
+}
+
+/// Like `CompoundImplication`, but with resolved tag/value names instead of ids, for display and
+/// for building the cycle-detection graph in `api::imply`.
+#[derive(Debug)]
+pub struct CompoundImplicationNamed {
+    pub id: ImplicationRuleId,
+    pub antecedents: Vec<(Tag, Option<Value>)>,
+    pub implied: (Tag, Option<Value>),
+}
+
+/// A named query string, persisted so it can be referenced from other queries by name (e.g.
+/// `:work and urgent`) instead of being retyped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedQuery {
+    pub name: String,
+    pub query: String,
+}
+
 pub fn validate_tag_name(name: &str) -> Result<()> {
     validate_name_helper("tag names", name)
 }
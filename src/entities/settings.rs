@@ -1,5 +1,10 @@
+use std::str::FromStr;
+
 use error_chain::ensure;
 
+use crate::entities::{
+    DirectoryFingerprintAlgorithm, FileFingerprintAlgorithm, SymlinkFingerprintAlgorithm,
+};
 use crate::errors::*;
 
 pub trait Setting {
@@ -7,6 +12,9 @@ pub trait Setting {
     fn as_bool(&self) -> bool;
     fn as_str(&self) -> String;
     fn set(&mut self, value: &str) -> Result<()>;
+    /// A short description of the accepted values, used by `config --defaults` to expose the
+    /// setting schema.
+    fn type_hint(&self) -> &str;
 }
 
 struct BooleanValue<'a> {
@@ -45,11 +53,18 @@ impl<'a> Setting for BooleanValue<'a> {
         };
         Ok(())
     }
+
+    fn type_hint(&self) -> &str {
+        "yes|no"
+    }
 }
 
 struct StringValue<'a> {
     name: &'a str,
     value: String,
+    /// Optional validator run before a new value is accepted. Used to reject fingerprint algorithm
+    /// names that the `fingerprint` module does not implement, rather than silently storing them.
+    validator: Option<fn(&str) -> Result<()>>,
 }
 
 impl<'a> StringValue<'a> {
@@ -57,6 +72,15 @@ impl<'a> StringValue<'a> {
         Self {
             name,
             value: value.to_owned(),
+            validator: None,
+        }
+    }
+
+    fn with_validator(name: &'a str, value: &str, validator: fn(&str) -> Result<()>) -> Self {
+        Self {
+            name,
+            value: value.to_owned(),
+            validator: Some(validator),
         }
     }
 }
@@ -79,9 +103,28 @@ impl<'a> Setting for StringValue<'a> {
             value != "",
             format!("setting '{}' must be specified", self.name())
         );
+        if let Some(validator) = self.validator {
+            validator(value)?;
+        }
         self.value = value.to_owned();
         Ok(())
     }
+
+    fn type_hint(&self) -> &str {
+        "string"
+    }
+}
+
+fn validate_file_fingerprint_algorithm(value: &str) -> Result<()> {
+    FileFingerprintAlgorithm::from_str(value).map(|_| ())
+}
+
+fn validate_directory_fingerprint_algorithm(value: &str) -> Result<()> {
+    DirectoryFingerprintAlgorithm::from_str(value).map(|_| ())
+}
+
+fn validate_symlink_fingerprint_algorithm(value: &str) -> Result<()> {
+    SymlinkFingerprintAlgorithm::from_str(value).map(|_| ())
 }
 
 pub struct Settings {
@@ -101,18 +144,21 @@ impl Settings {
         let defaults: Vec<Box<dyn Setting>> = vec![
             Box::new(BooleanValue::new(Self::AUTO_CREATE_TAGS, true)),
             Box::new(BooleanValue::new(Self::AUTO_CREATE_VALUES, true)),
-            Box::new(StringValue::new(
+            Box::new(StringValue::with_validator(
                 Self::DIRECTORY_FINGERPRINT_ALGORITHM,
                 "none",
+                validate_directory_fingerprint_algorithm,
             )),
-            Box::new(StringValue::new(
+            Box::new(StringValue::with_validator(
                 Self::FILE_FINGERPRINT_ALGORITHM,
                 "dynamic:SHA256",
+                validate_file_fingerprint_algorithm,
             )),
             Box::new(BooleanValue::new(Self::REPORT_DUPLICATES, true)),
-            Box::new(StringValue::new(
+            Box::new(StringValue::with_validator(
                 Self::SYMLINK_FINGERPRINT_ALGORITHM,
                 "follow",
+                validate_symlink_fingerprint_algorithm,
             )),
         ];
         Self { settings: defaults }
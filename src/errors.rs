@@ -14,13 +14,27 @@ error_chain! {
             description("Cannot open database")
             display("Cannot open database at '{}'", path.display())
         }
-        QueryParsingError(query: String) {
+        WrongPassphrase(path: PathBuf) {
+            description("Wrong passphrase")
+            display("Cannot open encrypted database at '{}': wrong passphrase", path.display())
+        }
+        QueryParsingError(query: String, offset: usize, expected: String) {
             description("Cannot parse query")
-            display("Cannot parse query '{}'", &query)
+            display(
+                "Cannot parse query: expected {} at column {}\n{}\n{}^",
+                expected,
+                query[..*offset].chars().count() + 1,
+                query,
+                " ".repeat(query[..*offset].chars().count()),
+            )
         }
         OsStringConversion(os_string: OsString) {
 
         }
+        UnsafePath(path: PathBuf, reason: String) {
+            description("Unsafe path")
+            display("Refusing to store unsafe path '{}': {}", path.display(), reason)
+        }
     }
     foreign_links {
         Chrono(chrono::ParseError);
@@ -1,11 +1,15 @@
-use std::ffi::OsString;
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::ops;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::rc::Rc;
 
 use crate::errors::*;
 
+/// Name of the directory a database stores its own files under, relative to the repository root.
+pub(crate) const DB_DIR_NAME: &str = ".tmsu";
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct AbsPath(PathBuf);
 
@@ -39,6 +43,36 @@ impl AbsPath {
 
         self.0.strip_prefix(base).ok()
     }
+
+    /// Compute a relative path from `base` to `self`, even when `self` is not a descendant of
+    /// `base`: find the longest common prefix of the two component sequences, emit one `ParentDir`
+    /// per remaining `base` component, then append `self`'s trailing components.
+    ///
+    /// E.g. for `base` = "/a/b/c" and `self` = "/a/b/sibling/x", this returns "../sibling/x".
+    fn relativize(&self, base: &CanonicalPath) -> PathBuf {
+        let target_components: Vec<_> = self.0.components().collect();
+        let base_components: Vec<_> = base.components().collect();
+
+        let common_len = target_components
+            .iter()
+            .zip(base_components.iter())
+            .take_while(|(t, b)| t == b)
+            .count();
+
+        // Not even the root (or, on Windows, the drive prefix) is shared, so no number of `..`
+        // components could ever reach `self` from `base`. Fall back to an absolute path instead.
+        if common_len == 0 {
+            return self.0.clone();
+        }
+
+        let up_count = base_components.len() - common_len;
+
+        let mut parts = Vec::with_capacity(up_count + (target_components.len() - common_len));
+        parts.resize(up_count, Component::ParentDir);
+        parts.extend_from_slice(&target_components[common_len..]);
+
+        parts.into_iter().collect()
+    }
 }
 
 // Make all the `Path` methods available on AbsPath
@@ -56,16 +90,42 @@ impl AsRef<Path> for AbsPath {
     }
 }
 
+impl serde::Serialize for AbsPath {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+/// Lexically normalize a path, collapsing `.` and `..` segments without touching the filesystem.
+///
+/// This works directly on `Path::components()` so it never round-trips through a `String` and is
+/// correct for paths that aren't valid UTF-8 as well as for Windows prefixes. The retained
+/// components are accumulated on a stack: the leading `Prefix`/`RootDir` form a fixed base, `.` is
+/// dropped, and a `..` pops the stack only when its top is a `Normal` component. For a relative
+/// path with nothing to cancel the `..` is kept, while for an absolute path already at its root the
+/// `..` is simply discarded. An empty result becomes `""`, matching the previous behaviour.
 fn clean(p: PathBuf) -> PathBuf {
-    // FIXME TODO: do not rely on path_clean, because:
-    // 1. It doesn't support Windows properly
-    // 2. It works on strings, but not on paths
-    // We could do something similar to https://doc.rust-lang.org/std/path/struct.Path.html#method.components
-    let s =
-        path_clean::clean(p.to_str().unwrap_or_else(|| {
-            panic!("Bug: path cannot be converted to a string: {}", p.display())
-        }));
-    PathBuf::from(s)
+    let mut stack: Vec<Component> = Vec::new();
+    for component in p.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => stack.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => match stack.last() {
+                Some(Component::Normal(_)) => {
+                    stack.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => stack.push(component),
+            },
+            Component::Normal(_) => stack.push(component),
+        }
+    }
+
+    let mut result = PathBuf::new();
+    for component in stack {
+        result.push(component);
+    }
+    result
 }
 
 /// Simple wrapper around PathBuf to enforce stronger typing.
@@ -121,6 +181,12 @@ fn canonicalize_or_clean(path: PathBuf) -> Result<PathBuf> {
     }
 }
 
+/// True if `path` is non-empty and made up entirely of `..` components.
+fn is_all_parent_dirs(path: &Path) -> bool {
+    let mut components = path.components().peekable();
+    components.peek().is_some() && components.all(|c| c == Component::ParentDir)
+}
+
 fn is_symlink(path: &Path) -> bool {
     if let Ok(metadata) = fs::symlink_metadata(path) {
         return metadata.file_type().is_symlink();
@@ -128,6 +194,76 @@ fn is_symlink(path: &Path) -> bool {
     false
 }
 
+/// Expand shell-like shorthand in the raw, logical path given to `ScopedPath::new`: a leading `~`
+/// or `~user` component becomes the relevant home directory, and a component made up of three or
+/// more dots (`...`, `....`, ...) becomes that many `ParentDir` components (`...` = `../..`).
+///
+/// This is a pre-pass over the textual components only; it runs before the symlink-aware walk
+/// below resolves anything against `base`. `anchor` is that same `base`, used only to check
+/// whether a real file or directory of a component's exact (literal, unexpanded) name already
+/// exists at the point it occurs in the path -- if so, the component is left as-is rather than
+/// expanded, to avoid surprising users.
+fn expand_shorthand(path: &Path, anchor: &Path) -> PathBuf {
+    let mut expanded = PathBuf::new();
+
+    // Tracks the literal (unexpanded) path built up so far, purely to check whether a real
+    // file/directory shadows the shorthand at each step.
+    let mut literal_so_far = if path.is_absolute() {
+        PathBuf::from("/")
+    } else {
+        anchor.to_path_buf()
+    };
+
+    for (index, component) in path.components().enumerate() {
+        if let Component::Normal(name) = component {
+            let literal_path = literal_so_far.join(name);
+
+            if !literal_path.exists() {
+                if let Some(name) = name.to_str() {
+                    if index == 0 {
+                        if let Some(home) = expand_tilde(name) {
+                            expanded = home;
+                            literal_so_far = literal_path;
+                            continue;
+                        }
+                    }
+
+                    if let Some(parent_count) = ndots_count(name) {
+                        for _ in 0..parent_count {
+                            expanded.push("..");
+                        }
+                        literal_so_far = literal_path;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        expanded.push(component);
+        literal_so_far.push(component);
+    }
+
+    expanded
+}
+
+/// If `name` is `~` or `~user`, return that user's home directory.
+fn expand_tilde(name: &str) -> Option<PathBuf> {
+    match name.strip_prefix('~')? {
+        "" => dirs::home_dir(),
+        user => users::get_user_by_name(user).map(|u| u.home_dir().to_path_buf()),
+    }
+}
+
+/// If `name` is made up of three or more dots, return how many `ParentDir` components it expands
+/// to (one fewer than the number of dots, e.g. `...` is `../..`).
+fn ndots_count(name: &str) -> Option<usize> {
+    if name.len() >= 3 && name.bytes().all(|b| b == b'.') {
+        Some(name.len() - 1)
+    } else {
+        None
+    }
+}
+
 pub fn resolve_path(path: &Path, follow_symlinks: bool) -> Result<PathBuf> {
     // Get metadata without following symlinks
     if follow_symlinks && is_symlink(path) {
@@ -142,12 +278,15 @@ pub fn resolve_path(path: &Path, follow_symlinks: bool) -> Result<PathBuf> {
 ///
 /// A `ScopedPath` knows about a `base` directory. If the logical path is within the `base`
 /// directory (possibly after cleaning up and resolving symlinks), then it is stored as a relative
-/// path (relative to `base`). Otherwise it is stored as an absolute, canonical path.
+/// path (relative to `base`). Otherwise, it is still stored relative to `base`, reaching out via
+/// one or more leading `..` components (see `AbsPath::relativize`), unless `base` and the path
+/// share no common ancestor at all (e.g. different Windows drives), in which case it falls back to
+/// an absolute, canonical path.
 ///
 /// This stored part, either relative or absolute, is accessible via the `inner()` method.
 ///
 /// See the documentation of `new()` for more details.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ScopedPath {
     base: Rc<CanonicalPath>,
     inner: PathBuf,
@@ -162,19 +301,22 @@ impl ScopedPath {
     /// The given `path` can be either relative or absolute. If relative, it is assumed to be
     /// relative to `base`, not to the current directory.
     ///
+    /// Before anything else, a leading `~`/`~user` component and any `...`-style "ndots" component
+    /// are expanded; see `expand_shorthand` for details.
+    ///
     /// E.g.:
     /// ```rust
     /// let base = Rc::new(CanonicalPath::new("/foo/bar").unwrap());
     /// assert_eq!(ScopedPath::new(base.clone(), "baz").unwrap().inner(), &Path::new("baz"));
     /// assert_eq!(ScopedPath::new(base.clone(), "/tmp/foo/bar/baz").unwrap().inner(), &Path::new("baz"));
-    /// assert_eq!(ScopedPath::new(base.clone(), "../baz").unwrap().inner(), &Path::new("/tmp/foo/baz"));
-    /// assert_eq!(ScopedPath::new(base.clone(), "/tmp/foo").unwrap().inner(), &Path::new("/tmp/foo"));
+    /// assert_eq!(ScopedPath::new(base.clone(), "../baz").unwrap().inner(), &Path::new("../baz"));
+    /// assert_eq!(ScopedPath::new(base.clone(), "/tmp/foo").unwrap().inner(), &Path::new("../../tmp/foo"));
     /// assert_eq!(ScopedPath::new(base.clone(), "./baz/.././dummy/../").unwrap().inner(), &Path::new("."));
     /// ```
     pub fn new<P: AsRef<Path>>(base: Rc<CanonicalPath>, path: P) -> Result<Self> {
         assert!(base.is_dir(), "The base must be a directory");
 
-        let path = path.as_ref().to_path_buf();
+        let path = expand_shorthand(path.as_ref(), &base);
 
         let mut growing = if path.is_relative() {
             base.to_path_buf()
@@ -204,11 +346,13 @@ impl ScopedPath {
         // Append the remaining components without resolving links
         growing.push(components.as_path());
 
-        // Get the relative part
+        // Get the relative part. If the path isn't a descendant of `base`, still store it
+        // compactly as a relative path reaching out via `..`, rather than as a full absolute path:
+        // this keeps the database portable across machines where only some shared parent differs.
         let abs_path = AbsPath::from(growing, &*base);
         let mut inner = match abs_path.rel_to(&*base) {
             Some(rel) => rel.to_path_buf(),
-            None => abs_path.0.clone(),
+            None => abs_path.relativize(&*base),
         };
 
         // Special case
@@ -229,6 +373,15 @@ impl ScopedPath {
     /// Note that `/` and `.` are handled in a special way: both base and name will contain the
     /// same value. This is done to keep compatibility with existing sqlite DBs.
     pub fn inner_as_dir_and_name(&self) -> (OsString, OsString) {
+        // When the whole inner path is made up of ".." components, it refers to an ancestor of
+        // `base` that `relativize` reached past, rather than to a file or directory within it.
+        // `Path::parent()`/`file_name()` are purely lexical and don't know that a ".." component's
+        // own "parent" is one level further up, so handle this case explicitly: the name is a
+        // single "..", and the dir is one more ".." than that.
+        if is_all_parent_dirs(&self.inner) {
+            return (self.inner.join("..").into_os_string(), OsString::from(".."));
+        }
+
         let mut base = match self.inner.parent() {
             Some(dir) => dir,
             // `None` is possible only if the path terminates in a root or prefix
@@ -242,20 +395,28 @@ impl ScopedPath {
         }
 
         let name = match self.inner.file_name() {
+            // The only remaining case for `file_name()` to return `None` is when we are at the
+            // root (the ".." case above has already been handled)
+            None => self.inner.as_os_str(),
             Some(n) => n,
-            None => {
-                // The only valid case for this situation is when we are at the root
-                assert!(
-                    !self.inner.ends_with(".."),
-                    "Invalid ScopedPath state (this is a bug)"
-                );
-                self.inner.as_os_str()
-            }
         };
 
         (base.as_os_str().to_owned(), name.to_owned())
     }
 
+    /// Like `new`, but additionally runs `auditor` over the resulting path, rejecting it if it is
+    /// unsafe to store in the database. Intended for the tagging commands, which insert rows via
+    /// `add_file_tag` and so must not be fooled into tagging a path outside the repository.
+    pub fn new_audited<P: AsRef<Path>>(
+        base: Rc<CanonicalPath>,
+        path: P,
+        auditor: &mut PathAuditor,
+    ) -> Result<Self> {
+        let scoped_path = Self::new(base, path)?;
+        auditor.audit(&scoped_path.base, &scoped_path.absolute)?;
+        Ok(scoped_path)
+    }
+
     pub fn inner(&self) -> &Path {
         &self.inner
     }
@@ -264,6 +425,26 @@ impl ScopedPath {
     pub fn contains_root(&self) -> bool {
         self.base.starts_with(self)
     }
+
+    /// Cheaply extend this path by one child component, without re-running the symlink-aware walk
+    /// from `base`.
+    ///
+    /// Only call this when `name` is already known to name a real, direct child of this path (e.g.
+    /// one yielded by reading this directory) rather than arbitrary user input: skipping the walk
+    /// means a symlink at `name` itself would not be detected.
+    pub(crate) fn join_child(&self, name: &OsStr) -> Self {
+        let inner = if self.inner == Path::new(".") {
+            PathBuf::from(name)
+        } else {
+            self.inner.join(name)
+        };
+
+        ScopedPath {
+            base: self.base.clone(),
+            inner,
+            absolute: AbsPath::from_unchecked(self.absolute.0.join(name)),
+        }
+    }
 }
 
 // Make all the `AbsPath` methods available on ScopedPath
@@ -287,13 +468,95 @@ impl AsRef<Path> for ScopedPath {
     }
 }
 
+/// Validates candidate paths before they are allowed to enter the tag database, analogous to
+/// Mercurial's `path_auditor`.
+///
+/// A path is rejected if any of its components is the database's own internal directory
+/// (`DB_DIR_NAME`), if it escapes the repository root via `..` while the auditor is in
+/// "no-escape" mode, or if one of its intermediate components is a symlink that crosses the
+/// repository boundary (reusing the `is_symlink` check above).
+///
+/// Directory prefixes that have already been found safe are cached in `safe_prefixes`, so a batch
+/// of audits sharing ancestors (e.g. a recursive tagging run) stats each ancestor at most once.
+pub struct PathAuditor {
+    no_escape: bool,
+    safe_prefixes: HashSet<PathBuf>,
+}
+
+impl PathAuditor {
+    pub fn new(no_escape: bool) -> Self {
+        Self {
+            no_escape,
+            safe_prefixes: HashSet::new(),
+        }
+    }
+
+    /// Check that `candidate` (an absolute path, generally already resolved against `base`) is
+    /// safe to store in the database.
+    pub fn audit(&mut self, base: &CanonicalPath, candidate: &AbsPath) -> Result<()> {
+        let candidate_path: &Path = candidate;
+
+        if self.no_escape && !candidate_path.starts_with(base) {
+            return Err(ErrorKind::UnsafePath(
+                candidate_path.to_path_buf(),
+                "escapes the repository root".to_owned(),
+            )
+            .into());
+        }
+
+        for component in candidate_path.components() {
+            if component.as_os_str() == DB_DIR_NAME {
+                return Err(ErrorKind::UnsafePath(
+                    candidate_path.to_path_buf(),
+                    format!("contains the reserved '{}' component", DB_DIR_NAME),
+                )
+                .into());
+            }
+        }
+
+        // Walk the ancestors from the root down, since a symlink further up the tree makes
+        // everything below it unsafe too; skip ones already known to be safe.
+        let mut ancestors: Vec<&Path> = candidate_path.ancestors().skip(1).collect();
+        ancestors.reverse();
+
+        for ancestor in ancestors {
+            if self.safe_prefixes.contains(ancestor) {
+                continue;
+            }
+
+            if ancestor.starts_with(base)
+                && is_symlink(ancestor)
+                && !ancestor
+                    .canonicalize()
+                    .map_or(false, |target| target.starts_with(base))
+            {
+                return Err(ErrorKind::UnsafePath(
+                    candidate_path.to_path_buf(),
+                    format!(
+                        "'{}' is a symlink crossing the repository boundary",
+                        ancestor.display()
+                    ),
+                )
+                .into());
+            }
+
+            self.safe_prefixes.insert(ancestor.to_path_buf());
+        }
+
+        Ok(())
+    }
+}
+
 pub trait CasedContains {
+    /// Selects the folding strategy used when `ignore_case` is set: `true` performs full Unicode
+    /// case folding (correct for cases `to_lowercase` gets wrong, such as German "ß" folding to
+    /// "ss" or the Greek final sigma), `false` performs a cheap ASCII-only fold. The blanket
+    /// implementation below covers every collection and keeps the default, so
+    /// `contains_for_case` always uses full Unicode folding; no type can select the ASCII fold.
     const CASE: bool = true;
 
     /// Return true if and only if `self` contains the `to_find` string.
-    /// Matching can be done in a case insensitive way by setting `ignore_case` to `true`. Note that
-    /// the concept of case is not very well defined in UTF-8, so it is expected that some corner cases
-    /// will not be handled properly by implementations.
+    /// Matching can be done in a case insensitive way by setting `ignore_case` to `true`.
     fn contains_for_case(&self, to_find: &str, ignore_case: bool) -> bool;
 }
 
@@ -304,17 +567,25 @@ where
     for<'a> &'a I: IntoIterator<Item = &'a T>,
 {
     fn contains_for_case(&self, to_find: &str, ignore_case: bool) -> bool {
-        let to_find = lowercase_or_owned(to_find, ignore_case);
+        let to_find = fold_for_case(to_find, ignore_case, Self::CASE);
         self.into_iter()
-            .any(|s| to_find == lowercase_or_owned(s.as_ref(), ignore_case))
+            .any(|s| to_find == fold_for_case(s.as_ref(), ignore_case, Self::CASE))
     }
 }
 
-fn lowercase_or_owned(string: &str, ignore_case: bool) -> String {
-    if ignore_case {
-        string.to_lowercase()
+/// Fold `string` for a case-insensitive comparison, or return it unchanged when `ignore_case` is
+/// `false`. `full_unicode` selects between full Unicode case folding (e.g. "ß" -> "ss", final
+/// sigma forms) and a cheap ASCII-only fold.
+///
+/// Both `string` and the values compared against it are always valid UTF-8 (`&str`, guaranteed by
+/// the type system), so this never has to deal with, or panic on, invalid byte sequences.
+fn fold_for_case(string: &str, ignore_case: bool, full_unicode: bool) -> String {
+    if !ignore_case {
+        string.to_owned()
+    } else if full_unicode {
+        caseless::default_case_fold_str(string)
     } else {
-        string.to_string()
+        string.to_ascii_lowercase()
     }
 }
 
@@ -390,14 +661,10 @@ mod tests {
         // Inside the root: relative
         assert_scoped_path(base.clone(), "rel", "rel");
         assert_scoped_path(base.clone(), join!(&root, "foo/bar"), "foo/bar");
-        // Outside the root: absolute
-        assert_scoped_path(base.clone(), "../other", join!(TESTS_ROOT, "other"));
-        assert_scoped_path(base.clone(), "foo/../../other", join!(TESTS_ROOT, "other"));
-        assert_scoped_path(
-            base.clone(),
-            join!(TESTS_ROOT, "dir"),
-            join!(TESTS_ROOT, "dir"),
-        );
+        // Outside the root: relative, reaching out via ".."
+        assert_scoped_path(base.clone(), "../other", "../other");
+        assert_scoped_path(base.clone(), "foo/../../other", "../other");
+        assert_scoped_path(base.clone(), join!(TESTS_ROOT, "dir"), "../dir");
 
         // Path clean up
         assert_scoped_path(base.clone(), "./dummy1/.././dummy2/../", ".");
@@ -422,6 +689,59 @@ mod tests {
         assert_scoped_path(base.clone(), join!(&root, "symlink-in/aa"), "symlink-in/aa");
     }
 
+    #[test]
+    fn test_join_child() {
+        let base = create_base();
+
+        let at_root = ScopedPath::new(base.clone(), ".").unwrap();
+        let child = at_root.join_child(OsStr::new("foo"));
+        assert_eq!(child.inner(), Path::new("foo"));
+        let child_path: &Path = &child;
+        assert_eq!(child_path, base.join("foo").as_path());
+
+        let grandchild = child.join_child(OsStr::new("bar"));
+        assert_eq!(grandchild.inner(), Path::new("foo/bar"));
+        let grandchild_path: &Path = &grandchild;
+        assert_eq!(grandchild_path, base.join("foo/bar").as_path());
+    }
+
+    #[test]
+    fn test_expand_shorthand_ndots() {
+        let root = join!(TESTS_ROOT, "ndots-root");
+        fs::create_dir_all(join!(&root, "a/b/c")).unwrap();
+        let base = Rc::new(CanonicalPath::new(join!(&root, "a/b/c")).unwrap());
+
+        // "..." expands to "../.." and "...." to "../../.."
+        let two_up = ScopedPath::new(base.clone(), "...").unwrap();
+        assert_eq!(two_up.absolute.0, join!(&root, "a"));
+        let three_up = ScopedPath::new(base.clone(), "....").unwrap();
+        assert_eq!(three_up.absolute.0, PathBuf::from(&root));
+
+        // A literal directory named "..." shadows the expansion and is kept as-is
+        fs::create_dir_all(join!(&root, "a/b/c/...")).unwrap();
+        let shadowed = ScopedPath::new(base.clone(), "...").unwrap();
+        assert_eq!(shadowed.inner, PathBuf::from("..."));
+    }
+
+    #[test]
+    fn test_expand_shorthand_tilde() {
+        let root = join!(TESTS_ROOT, "tilde-root");
+        let home = join!(&root, "home");
+        fs::create_dir_all(&home).unwrap();
+        let base = Rc::new(CanonicalPath::new(&root).unwrap());
+
+        std::env::set_var("HOME", &home);
+
+        // A bare "~" expands to $HOME
+        let scoped = ScopedPath::new(base.clone(), "~").unwrap();
+        assert_eq!(scoped.absolute.0, PathBuf::from(&home));
+
+        // A literal directory named "~" shadows the expansion and is kept as-is
+        fs::create_dir_all(join!(&root, "~")).unwrap();
+        let shadowed = ScopedPath::new(base.clone(), "~").unwrap();
+        assert_eq!(shadowed.inner, PathBuf::from("~"));
+    }
+
     #[test]
     fn test_inner_as_dir_and_name() {
         fn assert_dir_name(inner: &str, expected_dir: &str, expected_name: &str) {
@@ -437,15 +757,19 @@ mod tests {
         assert_dir_name("foo/bar/baz", "foo/bar", "baz");
         assert_dir_name("foo/bar/baz/", "foo/bar", "baz");
 
-        // Absolute paths
+        // Absolute paths outside the base: stored relative, reaching out via ".."
         fs::create_dir_all("/tmp/foo/bar/baz").unwrap();
-        assert_dir_name("/tmp/foo/bar", "/tmp/foo", "bar");
-        assert_dir_name("/tmp/foo/bar/baz", "/tmp/foo/bar", "baz");
-        assert_dir_name("/tmp/foo/bar/baz/", "/tmp/foo/bar", "baz");
+        assert_dir_name("/tmp/foo/bar", "../foo", "bar");
+        assert_dir_name("/tmp/foo/bar/baz", "../foo/bar", "baz");
+        assert_dir_name("/tmp/foo/bar/baz/", "../foo/bar", "baz");
 
         // Special cases
         assert_dir_name(".", ".", ".");
-        assert_dir_name("/", "/", "/");
+
+        // Paths that are themselves an ancestor of the base, reached by "relativize" (the
+        // filesystem root ends up two levels above TESTS_ROOT, hence "../..")
+        assert_dir_name("..", "../..", "..");
+        assert_dir_name("/", "../../..", "..");
     }
 
     #[test]
@@ -463,6 +787,43 @@ mod tests {
         assert_deref("/tmp/foo", &PathBuf::from("/tmp/foo"));
     }
 
+    #[test]
+    fn test_path_auditor() {
+        let root = join!(TESTS_ROOT, "auditor-root");
+        fs::create_dir_all(join!(&root, "sub")).unwrap();
+        let base = Rc::new(CanonicalPath::new(&root).unwrap());
+
+        let mut auditor = PathAuditor::new(true);
+
+        // A plain path within the root is safe
+        let ok_path = ScopedPath::new(base.clone(), "sub/file.txt").unwrap();
+        assert!(auditor.audit(&base, &ok_path).is_ok());
+        // Re-auditing a path under the same cached-safe directory still succeeds
+        let ok_path2 = ScopedPath::new(base.clone(), "sub/other.txt").unwrap();
+        assert!(auditor.audit(&base, &ok_path2).is_ok());
+
+        // A path whose components include the database's own directory is rejected
+        let db_path = ScopedPath::new(base.clone(), join!(DB_DIR_NAME, "db.sqlite")).unwrap();
+        assert!(auditor.audit(&base, &db_path).is_err());
+
+        // A path escaping the repository root is rejected while in no-escape mode
+        fs::create_dir_all(join!(TESTS_ROOT, "outside")).unwrap();
+        let outside = AbsPath::from_unchecked(join!(TESTS_ROOT, "outside/file.txt"));
+        assert!(auditor.audit(&base, &outside).is_err());
+        // ... but allowed once escaping is permitted
+        let mut lenient_auditor = PathAuditor::new(false);
+        assert!(lenient_auditor.audit(&base, &outside).is_ok());
+
+        // An intermediate component that is a symlink crossing the repository boundary is
+        // rejected
+        let link_target = join!(TESTS_ROOT, "auditor-link-target");
+        fs::create_dir_all(&link_target).unwrap();
+        let link = join!(&root, "link");
+        create_symlink(&link_target, &link);
+        let through_link = AbsPath::from_unchecked(join!(&root, "link/file.txt"));
+        assert!(auditor.audit(&base, &through_link).is_err());
+    }
+
     #[test]
     fn test_contains_for_case() {
         let vec = vec!["a", "B", "bc", "Côté"];
@@ -507,4 +868,14 @@ mod tests {
         };
         assert!(&[value].contains_for_case("ab", false));
     }
+
+    #[test]
+    fn test_contains_for_case_full_unicode_folding() {
+        let vec = vec!["straße"];
+
+        // Full Unicode case folding (the default) knows "ß" folds the same as "ss"
+        assert_eq!(true, vec.contains_for_case("STRASSE", true));
+        // ... but an exact, case-sensitive match does not
+        assert_eq!(false, vec.contains_for_case("STRASSE", false));
+    }
 }
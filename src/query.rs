@@ -9,6 +9,7 @@ pub(crate) enum Expression {
     And(AndExpression),
     Or(OrExpression),
     Comparison(ComparisonExpression),
+    Saved(SavedExpression),
 }
 
 #[derive(Debug, PartialEq)]
@@ -40,6 +41,13 @@ pub(crate) struct ComparisonExpression {
     pub value: String,
 }
 
+/// A reference to a saved query by name (e.g. `:work`), as parsed. Resolved to the expression it
+/// stands for by `Expression::resolve_saved` before the tree is used for anything else.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SavedExpression {
+    pub name: String,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Operator {
     Different,
@@ -58,9 +66,10 @@ impl Expression {
         }
 
         // Anything else -> Some(parsed_expression)
-        let (_, expr) = parser::parse_expr(query)
-            .map_err(|_| ErrorKind::QueryParsingError(query.to_owned()))?;
-        Ok(Some(expr))
+        match parser::parse_expr(query) {
+            Ok((_, expr)) => Ok(Some(expr)),
+            Err(err) => Err(parser::describe_parse_error(query, &err).into()),
+        }
     }
 
     pub(crate) fn tag_names(&self) -> Vec<&str> {
@@ -82,6 +91,9 @@ impl Expression {
                 or_expr.right.tag_names_rec(names);
             }
             Expression::Comparison(comp_expr) => names.push(&comp_expr.tag),
+            // `resolve_saved` replaces every `Saved` node before the tree is used for anything
+            // else, so by the time this runs there should be none left to contribute names.
+            Expression::Saved(_) => (),
         }
     }
 
@@ -110,6 +122,53 @@ impl Expression {
                 | Operator::MoreThan
                 | Operator::MoreThanOrEqual => (),
             },
+            Expression::Saved(_) => (),
+        }
+    }
+
+    /// Recursively replace every `Saved(name)` node with the expression stored under that name,
+    /// via `lookup`. Rejects (rather than looping forever on) a saved query that directly or
+    /// transitively references itself: `visiting` holds the names currently being expanded on the
+    /// path from the root, and a name met twice is a cycle.
+    pub(crate) fn resolve_saved(
+        self,
+        lookup: &mut impl FnMut(&str) -> Result<Option<String>>,
+        visiting: &mut Vec<String>,
+    ) -> Result<Self> {
+        match self {
+            Expression::Tag(_) | Expression::Comparison(_) => Ok(self),
+            Expression::Not(not_expr) => Ok(Expression::Not(NotExpression {
+                operand: Box::new(not_expr.operand.resolve_saved(lookup, visiting)?),
+            })),
+            Expression::And(and_expr) => Ok(Expression::And(AndExpression {
+                left: Box::new(and_expr.left.resolve_saved(lookup, visiting)?),
+                right: Box::new(and_expr.right.resolve_saved(lookup, visiting)?),
+            })),
+            Expression::Or(or_expr) => Ok(Expression::Or(OrExpression {
+                left: Box::new(or_expr.left.resolve_saved(lookup, visiting)?),
+                right: Box::new(or_expr.right.resolve_saved(lookup, visiting)?),
+            })),
+            Expression::Saved(saved_expr) => {
+                if visiting.contains(&saved_expr.name) {
+                    return Err(format!(
+                        "saved query '{}' is defined in terms of itself (via {})",
+                        saved_expr.name,
+                        visiting.join(" -> ")
+                    )
+                    .into());
+                }
+
+                let query = lookup(&saved_expr.name)?
+                    .ok_or_else(|| format!("no such saved query '{}'", saved_expr.name))?;
+                let inner = Expression::parse(&query)?
+                    .ok_or_else(|| format!("saved query '{}' is empty", saved_expr.name))?;
+
+                visiting.push(saved_expr.name.clone());
+                let resolved = inner.resolve_saved(lookup, visiting)?;
+                visiting.pop();
+
+                Ok(resolved)
+            }
         }
     }
 }
@@ -136,6 +195,87 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn expr_parse_error_points_at_failing_offset() {
+        let err = Expression::parse("a and ()").unwrap_err();
+        let message = err.to_string();
+
+        assert!(message.contains("column "), "{}", message);
+        assert!(message.contains("a and ()"), "{}", message);
+        assert!(message.contains('^'), "{}", message);
+    }
+
+    #[test]
+    fn expr_parse_error_points_at_failing_offset_with_multibyte_tag() {
+        // "café" has a multi-byte character before the failing "()", so the byte offset of the
+        // failure differs from its char offset; the column number and caret must both be measured
+        // in chars, or the caret would land past the opening parenthesis it's meant to point at.
+        let err = Expression::parse("café and ()").unwrap_err();
+        let message = err.to_string();
+
+        let query_line = message
+            .lines()
+            .find(|line| line.contains("café and ()"))
+            .unwrap();
+        let caret_line = message.lines().find(|line| line.contains('^')).unwrap();
+
+        let paren_char_index = query_line.chars().position(|c| c == '(').unwrap();
+        let caret_char_index = caret_line.chars().position(|c| c == '^').unwrap();
+        assert_eq!(paren_char_index, caret_char_index, "{}", message);
+        assert!(message.contains("column 10"), "{}", message);
+    }
+
+    #[test]
+    fn expr_resolve_saved_inlines_named_query() -> Result<()> {
+        let expr = Expression::parse(":work and urgent")?.unwrap();
+
+        let resolved = expr.resolve_saved(
+            &mut |name| {
+                Ok(match name {
+                    "work" => Some("project = acme".to_owned()),
+                    _ => None,
+                })
+            },
+            &mut vec![],
+        )?;
+
+        assert_eq!(
+            resolved,
+            Expression::And(AndExpression {
+                left: Box::new(Expression::Comparison(ComparisonExpression {
+                    tag: "project".to_owned(),
+                    operator: Operator::Equal,
+                    value: "acme".to_owned(),
+                })),
+                right: Box::new(Expression::Tag(TagExpression {
+                    tag: "urgent".to_owned()
+                })),
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn expr_resolve_saved_rejects_cycle() {
+        let expr = Expression::parse(":a").unwrap().unwrap();
+
+        let err = expr
+            .resolve_saved(
+                &mut |name| {
+                    Ok(match name {
+                        "a" => Some(":b".to_owned()),
+                        "b" => Some(":a".to_owned()),
+                        _ => None,
+                    })
+                },
+                &mut vec![],
+            )
+            .unwrap_err();
+
+        assert!(err.to_string().contains("itself"), "{}", err);
+    }
+
     #[test]
     fn expr_tag_names() -> Result<()> {
         let expr =
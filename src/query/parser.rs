@@ -6,9 +6,11 @@ use nom::multi::*;
 use nom::sequence::*;
 use nom::*;
 
+use crate::errors::ErrorKind;
+
 use super::{
     AndExpression, ComparisonExpression, Expression, NotExpression, Operator, OrExpression,
-    TagExpression,
+    SavedExpression, TagExpression,
 };
 
 pub(super) fn parse_whitespace(input: &str) -> IResult<&str, &str> {
@@ -23,11 +25,12 @@ pub(super) fn parse_expr(input: &str) -> IResult<&str, Expression> {
 //      FullExpr := Space* OrExpr Space*
 //      OrExpr := AndExpr (("or"|"OR") AndExpr)*
 //      AndExpr := AndOperand (("and"|"AND"|"") AndOperand)*
-//      AndOperand := (ParensExpr|ComparisonExpr|NotExpr|TagExpr)
+//      AndOperand := (ParensExpr|ComparisonExpr|NotExpr|SavedQueryExpr|TagExpr)
 //      ParensExpr := "(" Space* FullExpr Space* ")"
-//      NotExpr := ("not"|"NOT") (TagExpr|ComparisonExpr|ParensExpr)
+//      NotExpr := ("not"|"NOT") (TagExpr|ComparisonExpr|SavedQueryExpr|ParensExpr)
 //      TagExpr := TagName
 //      ComparisonExpr := TagName Operator ValueName
+//      SavedQueryExpr := ":" TagName
 //      TagName := TagChar+
 //      ValueName := TagChar+
 //      TagChar := EscapedChar|!SpecialChar
@@ -79,6 +82,12 @@ fn make_or(left: Expression, right: Expression) -> Expression {
     })
 }
 
+fn make_saved(name: &str) -> Expression {
+    Expression::Saved(SavedExpression {
+        name: name.to_owned(),
+    })
+}
+
 fn make_comparison(tag_name: &str, op: &str, value_name: &str) -> Expression {
     let operator = match op {
         "eq" | "EQ" | "Eq" | "eQ" | "=" | "==" => Operator::Equal,
@@ -118,15 +127,20 @@ where
     }
 }
 
-/// Parse a tag name: any escaped character or non-special character
-/// (special meaning whitespace, parenthesis or comparison character)
-fn tag_name(input: &str) -> IResult<&str, Expression> {
-    let parser = escaped_transform(
+/// Parse a raw name: any escaped character or non-special character (special meaning whitespace,
+/// parenthesis or comparison character). Shared between `tag_name` (which also rejects reserved
+/// keywords) and `saved_query_expr` (whose name isn't used in an operator or keyword slot).
+fn name_chars(input: &str) -> IResult<&str, String> {
+    escaped_transform(
         |input| take_till1(|c: char| SPECIAL_CHARS.contains(c) || c.is_whitespace())(input),
         '\\',
         |i: &str| take(1u8)(i),
-    );
+    )(input)
+}
 
+/// Parse a tag name: any escaped character or non-special character
+/// (special meaning whitespace, parenthesis or comparison character)
+fn tag_name(input: &str) -> IResult<&str, Expression> {
     // Check whether a parsed tag name is a keyword.
     // A simple equality check is not enough because we want to distinguish "or" from "\or". So we
     // also compare the length of the consumed string with the length of the keyword
@@ -137,7 +151,7 @@ fn tag_name(input: &str) -> IResult<&str, Expression> {
 
     // Convert the string to an Expression, and make sure that reserved keywords are not used.
     // Note that "." and ".." cannot be used in the VFS.
-    match parser(input) {
+    match name_chars(input) {
         Ok((s, tag)) => {
             for keyword in RESERVED_KEYWORDS {
                 if is_keyword(input.len(), s, &tag, keyword) {
@@ -150,6 +164,13 @@ fn tag_name(input: &str) -> IResult<&str, Expression> {
     }
 }
 
+/// Parse a saved-query reference: a `:` followed by the saved query's name, e.g. `:work`. The name
+/// uses the same character class as a tag name, but isn't checked against `RESERVED_KEYWORDS`,
+/// since it never appears in an operator or keyword slot.
+fn saved_query_expr(input: &str) -> IResult<&str, Expression> {
+    preceded(char(':'), name_chars)(input).map(|(s, name)| (s, make_saved(&name)))
+}
+
 fn comparison_expr(input: &str) -> IResult<&str, Expression> {
     // Textual operators are treated differently from symbol ones, as they require a space on the
     // left (and possibly right).
@@ -196,6 +217,7 @@ fn not_expr(input: &str) -> IResult<&str, Expression> {
     let not_operand = alt((
         preceded(white0, parens_expr),
         preceded(white1, comparison_expr),
+        preceded(white1, saved_query_expr),
         preceded(white1, tag_name),
     ));
     let parser = preceded(keyword("not"), not_operand);
@@ -207,7 +229,7 @@ fn not_expr(input: &str) -> IResult<&str, Expression> {
 /// The "and" keyword itself is optional.
 /// Note that a NOT expression is a valid AND expression when parsing.
 fn and_expr(input: &str) -> IResult<&str, Expression> {
-    let and_operand = alt((parens_expr, not_expr, comparison_expr, tag_name));
+    let and_operand = alt((parens_expr, not_expr, comparison_expr, saved_query_expr, tag_name));
     let optional_and = opt(tuple((keyword("and"), white0)));
     let and_keyword = tuple((white0, optional_and));
     let parser = tuple((&and_operand, many0(preceded(and_keyword, &and_operand))));
@@ -232,6 +254,42 @@ fn eof(input: &str) -> IResult<&str, ()> {
     not(take(1u8))(input)
 }
 
+/// Turn a failed `parse_expr` result into a position-aware `ErrorKind`, pointing at the byte
+/// offset where parsing gave up and naming what was expected there.
+///
+/// None of our combinators ever copy or reallocate the input, so the remaining slice carried by a
+/// nom error is always a suffix of the same `query` string; the offset is therefore just the
+/// difference between the two slices' start addresses.
+pub(super) fn describe_parse_error(
+    query: &str,
+    err: &Err<(&str, nom::error::ErrorKind)>,
+) -> ErrorKind {
+    let (remaining, kind) = match err {
+        Err::Error((remaining, kind)) | Err::Failure((remaining, kind)) => (*remaining, *kind),
+        // Our combinators are all built from the `complete` submodules, so this never actually
+        // happens; keep a harmless fallback rather than unwrapping.
+        Err::Incomplete(_) => (query, nom::error::ErrorKind::Tag),
+    };
+
+    let offset = (remaining.as_ptr() as usize).saturating_sub(query.as_ptr() as usize);
+    ErrorKind::QueryParsingError(query.to_owned(), offset, describe_expected(kind))
+}
+
+/// A short, human-readable description of what the failing combinator was looking for, to go
+/// alongside the caret in `ErrorKind::QueryParsingError`'s display.
+fn describe_expected(kind: nom::error::ErrorKind) -> String {
+    match kind {
+        nom::error::ErrorKind::Tag => "an operator, keyword or closing parenthesis",
+        nom::error::ErrorKind::Char => "a parenthesis",
+        nom::error::ErrorKind::TakeTill1 | nom::error::ErrorKind::Escaped => "a tag or value name",
+        nom::error::ErrorKind::Alt => "a tag name, comparison or parenthesized expression",
+        nom::error::ErrorKind::Many0 | nom::error::ErrorKind::Many1 => "another operand",
+        nom::error::ErrorKind::Not | nom::error::ErrorKind::Eof => "the end of the query",
+        _ => "a valid query token",
+    }
+    .to_owned()
+}
+
 /// Helper function to fold multiple Expression values (resulting from the parsing of an
 /// associative operator such as "and" or "or") into a single Expression.
 fn fold<F>(left: Expression, mut right: Vec<Expression>, merge: F) -> Expression
@@ -349,6 +407,20 @@ mod tests {
         assert!(comparison_expr("a =! 1").is_err());
     }
 
+    #[test]
+    fn can_parse_saved_query_expr() {
+        let assert_parse_saved =
+            |input, expected| assert_parse(saved_query_expr, input, &make_saved(expected));
+
+        assert_parse_saved(":work", "work");
+        assert_parse_saved(":work and urgent", "work");
+        assert_parse_saved(r":a\ b", "a b");
+
+        assert!(saved_query_expr("work").is_err());
+        assert!(saved_query_expr(": work").is_err());
+        assert!(saved_query_expr(":").is_err());
+    }
+
     #[test]
     fn can_parse_not_expr() {
         let assert_parse_not =
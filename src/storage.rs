@@ -1,20 +1,88 @@
 pub mod file;
 pub mod filetag;
+pub mod hierarchy;
+pub mod history;
 pub mod implication;
 pub mod meta;
+mod migrations;
+pub mod saved_query;
 mod schema;
 pub mod setting;
+pub mod status_cache;
 pub mod tag;
-mod upgrade;
 pub mod value;
 
+use std::collections::HashMap;
 use std::iter;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
 
-use crate::entities::{FileId, OptionalValueId, TagId, ValueId};
+use crate::entities::{FileId, ImplicationRuleId, OptionalValueId, TagId, ValueId};
 use crate::errors::*;
 use crate::path::CanonicalPath;
 
+/// Whether `create_or_open` should register SQL tracing/profiling on the connections it opens,
+/// set once at startup via `set_sql_tracing_enabled`. A plain atomic rather than something
+/// threaded through `create_or_open`'s signature, since doing the latter would mean plumbing a
+/// trace flag through every one of the many existing `Storage::open` call sites for a
+/// diagnostics-only feature.
+static TRACE_SQL_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Per-statement call counts and total wall-clock time accumulated by the profile hook, keyed by
+/// statement text. `None` while tracing is disabled, so the hook has nothing to lock and nothing
+/// to update.
+static SQL_PROFILE: Mutex<Option<HashMap<String, (u32, Duration)>>> = Mutex::new(None);
+
+/// Enable (or disable) SQL tracing and per-statement profiling for every `Storage` opened from
+/// this point on. Leave disabled (the default) and `create_or_open` registers no hooks at all, so
+/// there's zero overhead on the common path.
+pub fn set_sql_tracing_enabled(enabled: bool) {
+    TRACE_SQL_ENABLED.store(enabled, Ordering::Relaxed);
+    if enabled {
+        *SQL_PROFILE.lock().unwrap() = Some(HashMap::new());
+    }
+}
+
+fn sql_tracing_enabled() -> bool {
+    TRACE_SQL_ENABLED.load(Ordering::Relaxed)
+}
+
+fn trace_sql_statement(sql: &str) {
+    info!("SQL: {}", sql);
+}
+
+fn profile_sql_statement(sql: &str, duration: Duration) {
+    if let Some(stats) = SQL_PROFILE.lock().unwrap().as_mut() {
+        let entry = stats.entry(sql.to_owned()).or_insert((0, Duration::default()));
+        entry.0 += 1;
+        entry.1 += duration;
+    }
+}
+
+/// Log the statements profiled since tracing was enabled, slowest total time first, so the
+/// queries dominating a command's runtime are easy to spot. A no-op while tracing is disabled.
+fn log_sql_profile_summary() {
+    if !sql_tracing_enabled() {
+        return;
+    }
+
+    let profile = SQL_PROFILE.lock().unwrap();
+    let stats = match profile.as_ref() {
+        Some(stats) if !stats.is_empty() => stats,
+        _ => return,
+    };
+
+    let mut rows: Vec<_> = stats.iter().collect();
+    rows.sort_by(|a, b| (b.1).1.cmp(&(a.1).1));
+
+    info!("SQL profile since tracing was enabled:");
+    for (sql, (count, total)) in rows {
+        info!("  {:>8.3}s  x{:<4} {}", total.as_secs_f64(), count, sql.trim());
+    }
+}
+
 pub struct Storage {
     pub db_path: CanonicalPath,
     pub root_path: PathBuf,
@@ -24,21 +92,49 @@ pub struct Storage {
 impl Storage {
     pub fn create_at(db_path: &Path) -> Result<()> {
         info!("Creating database at {}", db_path.display());
-        Self::create_or_open(db_path)?;
+        Self::create_or_open(db_path, None)?;
         Ok(())
     }
 
     pub fn open(db_path: &Path) -> Result<Self> {
         info!("Opening database at {}", db_path.display());
-        Self::create_or_open(db_path)
+        Self::create_or_open(db_path, None)
+    }
+
+    /// Like `create_at`, but for a database encrypted with SQLCipher: `passphrase` is issued as
+    /// the `key` PRAGMA immediately after the connection is opened, before any other statement.
+    #[cfg(feature = "sqlcipher")]
+    pub fn create_at_encrypted(db_path: &Path, passphrase: &str) -> Result<()> {
+        info!("Creating encrypted database at {}", db_path.display());
+        Self::create_or_open(db_path, Some(passphrase))?;
+        Ok(())
+    }
+
+    /// Like `open`, but for a database encrypted with SQLCipher. A wrong `passphrase` surfaces as
+    /// `ErrorKind::WrongPassphrase` rather than the generic access failure a plaintext open would
+    /// give for a corrupt file, since SQLCipher reports an unreadable header either way.
+    #[cfg(feature = "sqlcipher")]
+    pub fn open_encrypted(db_path: &Path, passphrase: &str) -> Result<Self> {
+        info!("Opening encrypted database at {}", db_path.display());
+        Self::create_or_open(db_path, Some(passphrase))
     }
 
     /// Open a sqlite3 DB file, also creating it if it doesn't already exist.
     /// Note that the parent directory will NOT be created if it doesn't exist.
-    fn create_or_open(db_path: &Path) -> Result<Self> {
-        let conn = rusqlite::Connection::open(&db_path)
+    fn create_or_open(db_path: &Path, passphrase: Option<&str>) -> Result<Self> {
+        let mut conn = rusqlite::Connection::open(&db_path)
             .map_err(|_| ErrorKind::DatabaseAccessError(db_path.to_path_buf()))?;
 
+        // The key PRAGMA must be the very first statement run on the connection, before even the
+        // user_version read below, or SQLCipher has already committed to treating the file as
+        // unencrypted (or encrypted with the wrong key).
+        apply_passphrase(&conn, passphrase)?;
+
+        if sql_tracing_enabled() {
+            conn.trace(Some(trace_sql_statement));
+            conn.profile(Some(profile_sql_statement));
+        }
+
         // Use a canonical path to avoid issues such as #168
         let db_path = CanonicalPath::new(db_path)
             .map_err(|_| ErrorKind::NoDatabaseFound(db_path.to_path_buf()))?;
@@ -49,7 +145,9 @@ impl Storage {
             conn,
         };
 
-        res.upgrade_database()?;
+        if let Err(e) = res.upgrade_database() {
+            return Err(map_wrong_passphrase(e, passphrase.is_some(), res.db_path.as_ref()));
+        }
 
         Ok(res)
     }
@@ -60,14 +158,166 @@ impl Storage {
         })
     }
 
+    /// Rebuild the database file, reclaiming space left behind by deleted rows.
+    /// Note that SQLite forbids running VACUUM inside a transaction, so this operates directly on
+    /// the connection and must be called after any enclosing transaction has been committed.
+    pub fn vacuum(&mut self) -> Result<()> {
+        self.conn.execute("VACUUM", Transaction::NO_PARAMS)?;
+        Ok(())
+    }
+
     fn upgrade_database(&mut self) -> Result<()> {
         let mut tx = self.begin_transaction()?;
 
-        upgrade::upgrade(&mut tx)?;
+        migrations::migrate(&mut tx)?;
 
         tx.commit()?;
         Ok(())
     }
+
+    /// The schema version of this database, as recorded by the last migration applied to it.
+    pub fn schema_version(&mut self) -> Result<i64> {
+        self.begin_transaction()?.user_version()
+    }
+
+    /// The highest schema version this build of tmsu understands, i.e. the version a database
+    /// ends up at after `create_or_open`'s migrations run.
+    pub fn latest_schema_version() -> i64 {
+        migrations::CURRENT_VERSION
+    }
+
+    /// Copy this database's full contents into a fresh standalone database at `dest`, using
+    /// SQLite's online backup API so the copy is consistent even while this connection stays open
+    /// for writes. `on_progress` is called after each step with (pages remaining, total pages).
+    pub fn backup_to(&self, dest: &Path, on_progress: &mut dyn FnMut(i32, i32)) -> Result<()> {
+        // Go through create_or_open so `dest` ends up a valid standalone database (right
+        // directory checks, canonical path, schema migrated) before the backup overwrites its
+        // contents page-by-page.
+        Self::create_at(dest)?;
+        let mut dest_conn = rusqlite::Connection::open(dest)?;
+
+        run_backup(&self.conn, &mut dest_conn, on_progress)
+    }
+
+    /// Overwrite this database in place with the contents of `src`, via the same online backup
+    /// mechanism as `backup_to`, run in reverse: `src` is the live source and `self` the
+    /// destination.
+    pub fn restore_from(&mut self, src: &Path, on_progress: &mut dyn FnMut(i32, i32)) -> Result<()> {
+        let src_conn = rusqlite::Connection::open(src)?;
+
+        run_backup(&src_conn, &mut self.conn, on_progress)
+    }
+
+    /// Invert and re-apply the most recently recorded changeset, undoing whatever write it
+    /// covers, and drop it from the undo stack. Returns `false` if the stack was empty.
+    pub fn undo_last(&mut self) -> Result<bool> {
+        let mut tx = self.begin_transaction()?;
+
+        let entry = match history::most_recent_changeset(&mut tx)? {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+
+        let changeset = rusqlite::session::Changeset::from(entry.changeset.as_slice());
+        let inverted = changeset.invert()?;
+        apply_changeset_resolving_conflicts(&tx.tx, &inverted)?;
+        history::delete_history_entry(&mut tx, entry.id)?;
+
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Merge a changeset exported from another tmsu database (e.g. via its own undo history, or a
+    /// deliberately exported one) into this one. A `tag`/`value` row that collides with one
+    /// already here on its unique name is resolved by keeping the row already present, the same
+    /// outcome a direct `INSERT OR IGNORE` would reach, rather than aborting the whole merge.
+    pub fn apply_changeset(&mut self, changeset: &[u8]) -> Result<()> {
+        let mut tx = self.begin_transaction()?;
+
+        let changeset = rusqlite::session::Changeset::from(changeset);
+        apply_changeset_resolving_conflicts(&tx.tx, &changeset)?;
+
+        tx.commit()
+    }
+}
+
+/// Tables whose row-level changes are tracked for undo and cross-database merging.
+const CHANGE_TRACKED_TABLES: &[&str] = &["file", "tag", "value", "file_tag"];
+
+/// Apply `changeset` to `conn`, resolving conflicts the same way `resolve_uniqueness_conflict`
+/// does: the rest of the changeset still lands even when one row collides on a unique name.
+fn apply_changeset_resolving_conflicts(
+    conn: &rusqlite::Connection,
+    changeset: &rusqlite::session::Changeset,
+) -> Result<()> {
+    conn.apply(changeset, None::<fn(&str) -> bool>, resolve_uniqueness_conflict)?;
+    Ok(())
+}
+
+/// Resolve a changeset-apply conflict the way concurrent direct writers already would: a
+/// CONSTRAINT violation on `tag`/`value`'s unique name index means a row with that name already
+/// exists here, so the incoming change is omitted rather than aborting the whole undo/merge.
+/// Anything else (a row missing on one side, a genuine data conflict) is rare enough in practice
+/// that aborting is safer than guessing at a resolution.
+fn resolve_uniqueness_conflict(
+    conflict_type: rusqlite::session::ConflictType,
+    _conflicting: rusqlite::session::ConflictIter,
+) -> rusqlite::session::Action {
+    match conflict_type {
+        rusqlite::session::ConflictType::Constraint => rusqlite::session::Action::SqliteChangesetOmit,
+        _ => rusqlite::session::Action::SqliteChangesetAbort,
+    }
+}
+
+/// Step an online backup from `src` to `dest` to completion, reporting (pages remaining, total
+/// pages) after every step.
+fn run_backup(
+    src: &rusqlite::Connection,
+    dest: &mut rusqlite::Connection,
+    on_progress: &mut dyn FnMut(i32, i32),
+) -> Result<()> {
+    let backup = rusqlite::backup::Backup::new(src, dest)?;
+    let progress = |p: rusqlite::backup::Progress| on_progress(p.remaining, p.pagecount);
+    backup.run_to_completion(100, std::time::Duration::from_millis(25), Some(progress))?;
+    Ok(())
+}
+
+#[cfg(feature = "sqlcipher")]
+fn apply_passphrase(conn: &rusqlite::Connection, passphrase: Option<&str>) -> Result<()> {
+    if let Some(passphrase) = passphrase {
+        conn.pragma_update(None, "key", passphrase)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+fn apply_passphrase(_conn: &rusqlite::Connection, passphrase: Option<&str>) -> Result<()> {
+    if passphrase.is_some() {
+        return Err("this build of tmsu was not compiled with SQLCipher support".into());
+    }
+    Ok(())
+}
+
+/// If `passphrase_given`, reinterpret a "file is not a database" failure from `err` as a wrong
+/// passphrase rather than the generic access failure a plaintext open would report for the same
+/// underlying SQLite error: SQLCipher can't distinguish a corrupt file from a wrong key, since
+/// either way it fails to find a valid header once it starts reading with that key.
+fn map_wrong_passphrase(err: Error, passphrase_given: bool, db_path: &Path) -> Error {
+    if !passphrase_given {
+        return err;
+    }
+
+    let is_not_a_database = matches!(
+        err.kind(),
+        ErrorKind::Rusqlite(rusqlite::Error::SqliteFailure(ffi_err, _))
+            if ffi_err.code == rusqlite::ErrorCode::NotADatabase
+    );
+
+    if is_not_a_database {
+        ErrorKind::WrongPassphrase(db_path.to_path_buf()).into()
+    } else {
+        err
+    }
 }
 
 fn determine_root_path(db_path: &CanonicalPath) -> Result<PathBuf> {
@@ -77,7 +327,7 @@ fn determine_root_path(db_path: &CanonicalPath) -> Result<PathBuf> {
         // If a directory has a name, parent_opt cannot be None
         let parent = parent_opt.unwrap();
 
-        if dir_name == ".tmsu" {
+        if dir_name == crate::path::DB_DIR_NAME {
             // The unwrap() call should never fail for a canonical path
             Ok(parent.parent().unwrap().to_path_buf())
         } else {
@@ -99,7 +349,9 @@ pub struct Transaction<'a> {
 // (e.g. Statement or ToSql).
 impl<'a> Transaction<'a> {
     pub fn commit(self) -> Result<()> {
-        Ok(self.tx.commit()?)
+        self.tx.commit()?;
+        log_sql_profile_summary();
+        Ok(())
     }
 
     // The helper functions below are not public, to be usable only from submodules.
@@ -138,7 +390,10 @@ impl<'a> Transaction<'a> {
         P::Item: rusqlite::ToSql,
         F: Fn(Row<'_>) -> Result<T>,
     {
-        let mut stmt = self.tx.prepare(sql)?;
+        // Cached, since commands like `status` run the exact same SELECT once per path across a
+        // large tree: the cache lives on the underlying connection, so it's shared across
+        // transactions rather than being rebuilt every time one is opened.
+        let mut stmt = self.tx.prepare_cached(sql)?;
         let mut rows = stmt.query(params)?;
 
         let mut objects = Vec::new();
@@ -153,7 +408,7 @@ impl<'a> Transaction<'a> {
     where
         F: FnOnce(Row<'_>) -> Result<T>,
     {
-        let mut stmt = self.tx.prepare(sql)?;
+        let mut stmt = self.tx.prepare_cached(sql)?;
         let mut rows = stmt.query(Self::NO_PARAMS)?;
 
         rows.next()?.map(|r| Row::new(r)).map(f).transpose()
@@ -167,27 +422,126 @@ FROM {}",
             table_name
         );
 
-        let value: u32 = self.tx.query_row(&sql, Self::NO_PARAMS, |row| row.get(0))?;
+        let mut stmt = self.tx.prepare_cached(&sql)?;
+        let value: u32 = stmt.query_row(Self::NO_PARAMS, |row| row.get(0))?;
         Ok(value as u64)
     }
 
     fn last_inserted_row_id(&mut self) -> u32 {
         self.tx.last_insert_rowid() as u32
     }
-}
 
-/// Generate a string such as "?,?,?", with as many placeholders ('?') as requested
-fn generate_placeholders<'a>(values: &'a [&str]) -> Result<(String, Vec<&'a dyn rusqlite::ToSql>)> {
-    error_chain::ensure!(!values.is_empty(), "Bug: expected at least one placeholder");
-    let placeholders: Vec<_> = iter::repeat("?").take(values.len()).collect();
-    placeholders.join(",");
+    /// Read SQLite's `PRAGMA user_version`, which `migrations::migrate` uses to track how far a
+    /// database's schema has been brought forward. A freshly created database reads as 0.
+    fn user_version(&mut self) -> Result<i64> {
+        Ok(self.tx.pragma_query_value(None, "user_version", |row| row.get(0))?)
+    }
+
+    /// Set `PRAGMA user_version`, recording that every migration up to and including `version` has
+    /// been applied.
+    fn set_user_version(&mut self, version: i64) -> Result<()> {
+        Ok(self.tx.pragma_update(None, "user_version", version)?)
+    }
+
+    /// Start recording every row-level change `CHANGE_TRACKED_TABLES` undergoes from this point
+    /// in the transaction onward. Pair with `capture_change_session`, called once the write is
+    /// done but still within the same transaction, so the history row lands atomically with the
+    /// change it describes.
+    pub(crate) fn begin_change_session(&self) -> Result<rusqlite::session::Session<'_>> {
+        let mut session = rusqlite::session::Session::new(&self.tx)?;
+        for table in CHANGE_TRACKED_TABLES {
+            session.attach(Some(table))?;
+        }
+        Ok(session)
+    }
+
+    /// Export `session` as a changeset and, if it recorded anything, push it onto the undo stack
+    /// (`storage::history`) tagged with `operation`. A session that saw no changes is dropped
+    /// without a history row, so a command that begins one defensively (e.g. because it isn't sure
+    /// up front whether it will write anything) doesn't clutter the undo stack.
+    pub(crate) fn capture_change_session(
+        &mut self,
+        mut session: rusqlite::session::Session<'_>,
+        operation: &str,
+    ) -> Result<()> {
+        if session.is_empty() {
+            return Ok(());
+        }
 
-    let mut params = Vec::with_capacity(values.len());
-    for value in values {
-        params.push(value as &dyn rusqlite::ToSql);
+        let bytes = session.changeset()?.as_slice().to_vec();
+        drop(session);
+
+        history::record_changeset(self, operation, &bytes)
+    }
+
+    /// SQLite caps bound parameters per statement at `SQLITE_MAX_VARIABLE_NUMBER` (999 by
+    /// default). Batches are kept a little under that so a handful of leading fixed params still
+    /// leave room in the worst case.
+    const MAX_CHUNK_SIZE: usize = 900;
+
+    /// Like `query_vec_params`, but for a query whose bound `IN (...)` list may hold more values
+    /// than fit in a single statement. `values` is split into batches of at most
+    /// `MAX_CHUNK_SIZE`; for each batch, `sql_for` is handed a freshly generated placeholder list
+    /// (e.g. `"?,?,?"`) sized to that batch and must return the full SQL text with it spliced in.
+    /// `leading_params` are re-bound ahead of every batch's own values (e.g. a tag id that's the
+    /// same across every batch of file ids). Results from every batch are concatenated, in order.
+    fn query_vec_chunked<T, V, F>(
+        &mut self,
+        leading_params: &[&dyn rusqlite::ToSql],
+        values: &[V],
+        sql_for: impl Fn(&str) -> String,
+        f: F,
+    ) -> Result<Vec<T>>
+    where
+        V: rusqlite::ToSql,
+        F: Fn(Row<'_>) -> Result<T>,
+    {
+        let mut results = Vec::new();
+        for chunk in values.chunks(Self::MAX_CHUNK_SIZE) {
+            let sql = sql_for(&placeholders_for(chunk.len()));
+            let params = chunked_params(leading_params, chunk);
+            results.extend(self.query_vec_params(&sql, &params, &f)?);
+        }
+
+        Ok(results)
     }
 
-    Ok((placeholders.join(","), params))
+    /// Like `query_vec_chunked`, but for a statement run for effect (`INSERT`/`UPDATE`/`DELETE`):
+    /// returns the sum of affected rows across every batch instead of concatenating result rows.
+    fn execute_chunked<V>(
+        &mut self,
+        leading_params: &[&dyn rusqlite::ToSql],
+        values: &[V],
+        sql_for: impl Fn(&str) -> String,
+    ) -> Result<usize>
+    where
+        V: rusqlite::ToSql,
+    {
+        let mut affected = 0;
+        for chunk in values.chunks(Self::MAX_CHUNK_SIZE) {
+            let sql = sql_for(&placeholders_for(chunk.len()));
+            let params = chunked_params(leading_params, chunk);
+            affected += self.execute_params(&sql, &params)?;
+        }
+
+        Ok(affected)
+    }
+}
+
+/// Bind `leading_params` ahead of one chunk's own values, for a single batch of a chunked query.
+fn chunked_params<'a, V: rusqlite::ToSql>(
+    leading_params: &[&'a dyn rusqlite::ToSql],
+    chunk: &'a [V],
+) -> Vec<&'a dyn rusqlite::ToSql> {
+    let mut params = Vec::with_capacity(leading_params.len() + chunk.len());
+    params.extend_from_slice(leading_params);
+    params.extend(chunk.iter().map(|v| v as &dyn rusqlite::ToSql));
+    params
+}
+
+/// Generate a string such as "?,?,?", with as many placeholders ('?') as requested
+fn placeholders_for(count: usize) -> String {
+    iter::repeat("?").take(count).collect::<Vec<_>>().join(",")
 }
 
 /// Simple wrapper around a rusqlite::Row, mostly to avoid explicit error conversions in callbacks.
@@ -253,3 +607,15 @@ impl rusqlite::ToSql for FileId {
         self.0.to_sql()
     }
 }
+
+impl rusqlite::types::FromSql for ImplicationRuleId {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        u32::column_result(value).map(ImplicationRuleId)
+    }
+}
+
+impl rusqlite::ToSql for ImplicationRuleId {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        self.0.to_sql()
+    }
+}
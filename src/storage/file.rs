@@ -64,6 +64,53 @@ ORDER BY directory || '/' || name";
     tx.query_vec_params(&sql, params, parse_file)
 }
 
+/// Relocate a file record by rewriting its directory and name, leaving its fingerprint and
+/// metadata untouched. Used by manual repair when the file does not (yet) exist at the new path.
+pub fn update_file_path(
+    tx: &mut Transaction,
+    file_id: &FileId,
+    scoped_path: &ScopedPath,
+) -> Result<()> {
+    let sql = "
+UPDATE file
+SET directory = ?, name = ?
+WHERE id = ?";
+
+    let (dir, name) = scoped_path.inner_as_dir_and_name();
+    let params = rusqlite::params![path_to_sql(dir)?, path_to_sql(name)?, file_id];
+    match tx.execute_params(sql, params) {
+        Ok(1) => Ok(()),
+        Ok(_) => Err("Expected exactly one row to be affected".into()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Return every file that shares a non-empty fingerprint with at least one other file, ordered by
+/// fingerprint so that callers can group consecutive rows into duplicate clusters. When `path` is
+/// given, the search is restricted to files stored under it.
+pub fn duplicate_files(tx: &mut Transaction, path: Option<&ScopedPath>) -> Result<Vec<File>> {
+    let mut builder = SqlBuilder::new();
+    builder.append_sql(
+        "
+SELECT id, directory, name, fingerprint, mod_time, size, is_dir
+FROM file
+WHERE fingerprint != ''
+AND fingerprint IN (SELECT fingerprint
+                    FROM file
+                    WHERE fingerprint != ''
+                    GROUP BY fingerprint
+                    HAVING count(*) > 1)",
+    );
+
+    if let Some(path) = path {
+        build_path_clause(&mut builder, path)?;
+    }
+
+    builder.append_sql("ORDER BY fingerprint, directory || '/' || name");
+
+    tx.query_vec_params(&builder.sql(), builder.params(), parse_file)
+}
+
 fn parse_file(row: Row) -> Result<File> {
     let mod_time_str: String = row.get(4)?;
     let mod_time = DateTime::parse_from_str(&mod_time_str, TIMESTAMP_FORMAT)?;
@@ -76,34 +123,69 @@ fn parse_file(row: Row) -> Result<File> {
         mod_time,
         size: row.get_u64(5)?,
         is_dir: row.get(6)?,
+        // No column of the `file` table stores the mtime_ambiguous flag, so every row reads as
+        // unflagged.
+        mtime_ambiguous: false,
     })
 }
 
 pub fn delete_untagged_files(tx: &mut Transaction, file_ids: &[FileId]) -> Result<()> {
-    let sql = "
+    // Chunked since `file_ids` can grow as large as a single untag/merge/delete operation's whole
+    // file set, which could in principle exceed SQLite's bound-parameter limit.
+    tx.execute_chunked(&[], file_ids, |placeholders| {
+        format!(
+            "
 DELETE FROM file
-WHERE id = ?1
-AND (SELECT count(1)
-     FROM file_tag
-     WHERE file_id = ?1) == 0";
-
-    for file_id in file_ids {
-        let params = rusqlite::params![file_id];
-        tx.execute_params(sql, params)?;
-    }
+WHERE id IN ({})
+AND id NOT IN (SELECT DISTINCT file_id FROM file_tag)",
+            placeholders
+        )
+    })?;
 
     Ok(())
 }
 
+/// Count the files which have no associated file-tags, i.e. those that can be reclaimed.
+pub fn untagged_file_count(tx: &mut Transaction) -> Result<u64> {
+    let sql = "
+SELECT count(1)
+FROM file
+WHERE id NOT IN (SELECT DISTINCT file_id FROM file_tag)";
+
+    Ok(tx.query_single(sql, |row| row.get::<_, u32>(0))?.unwrap_or(0) as u64)
+}
+
+/// Delete every file which has no associated file-tags, returning the number of rows removed.
+pub fn delete_all_untagged_files(tx: &mut Transaction) -> Result<u64> {
+    let sql = "
+DELETE FROM file
+WHERE id NOT IN (SELECT DISTINCT file_id FROM file_tag)";
+
+    Ok(tx.execute(sql)? as u64)
+}
+
 pub(crate) fn files_for_query(
     tx: &mut Transaction,
     expression: Option<&Expression>,
     explicit_only: bool,
     ignore_case: bool,
+    include_hierarchy: bool,
     path: Option<&ScopedPath>,
     file_sort: Option<FileSort>,
 ) -> Result<Vec<File>> {
-    let builder = build_query(expression, explicit_only, ignore_case, path, file_sort)?;
+    // Old databases that predate the materialized closure lack the table; in that case the branch
+    // builders fall back to the recursive CTE so queries keep working until `repair` populates it.
+    let use_closure = crate::storage::implication::closure_table_exists(tx)?;
+
+    let builder = build_query(
+        expression,
+        explicit_only,
+        ignore_case,
+        include_hierarchy,
+        use_closure,
+        path,
+        file_sort,
+    )?;
 
     tx.query_vec_params(&builder.sql(), builder.params(), parse_file)
 }
@@ -112,6 +194,8 @@ fn build_query(
     expression: Option<&Expression>,
     explicit_only: bool,
     ignore_case: bool,
+    include_hierarchy: bool,
+    use_closure: bool,
     path: Option<&ScopedPath>,
     file_sort: Option<FileSort>,
 ) -> Result<SqlBuilder<'static>> {
@@ -124,7 +208,14 @@ FROM file
 WHERE",
     );
     if let Some(expr) = expression {
-        build_query_branch(&mut builder, expr, explicit_only, ignore_case);
+        build_query_branch(
+            &mut builder,
+            expr,
+            explicit_only,
+            ignore_case,
+            include_hierarchy,
+            use_closure,
+        );
     } else {
         builder.append_sql("1 == 1");
     }
@@ -143,23 +234,48 @@ fn build_query_branch(
     expression: &Expression,
     explicit_only: bool,
     ignore_case: bool,
+    include_hierarchy: bool,
+    use_closure: bool,
 ) {
     match expression {
-        Expression::Not(not_expr) => {
-            build_not_query_branch(builder, not_expr, explicit_only, ignore_case)
-        }
-        Expression::And(and_expr) => {
-            build_and_query_branch(builder, and_expr, explicit_only, ignore_case)
-        }
-        Expression::Or(or_expr) => {
-            build_or_query_branch(builder, or_expr, explicit_only, ignore_case)
-        }
-        Expression::Tag(tag_expr) => {
-            build_tag_query_branch(builder, tag_expr, explicit_only, ignore_case)
-        }
+        Expression::Not(not_expr) => build_not_query_branch(
+            builder,
+            not_expr,
+            explicit_only,
+            ignore_case,
+            include_hierarchy,
+            use_closure,
+        ),
+        Expression::And(and_expr) => build_and_query_branch(
+            builder,
+            and_expr,
+            explicit_only,
+            ignore_case,
+            include_hierarchy,
+            use_closure,
+        ),
+        Expression::Or(or_expr) => build_or_query_branch(
+            builder,
+            or_expr,
+            explicit_only,
+            ignore_case,
+            include_hierarchy,
+            use_closure,
+        ),
+        Expression::Tag(tag_expr) => build_tag_query_branch(
+            builder,
+            tag_expr,
+            explicit_only,
+            ignore_case,
+            include_hierarchy,
+            use_closure,
+        ),
         Expression::Comparison(comp_expr) => {
-            build_comp_query_branch(builder, comp_expr, explicit_only, ignore_case)
+            build_comp_query_branch(builder, comp_expr, explicit_only, ignore_case, use_closure)
         }
+        // `Expression::resolve_saved` inlines every `Saved` node before a query is ever built, so
+        // none should reach the query builder.
+        Expression::Saved(_) => unreachable!("Bug: Saved expressions should already be resolved"),
     };
 }
 
@@ -168,9 +284,18 @@ fn build_not_query_branch(
     not_expr: &NotExpression,
     explicit_only: bool,
     ignore_case: bool,
+    include_hierarchy: bool,
+    use_closure: bool,
 ) {
     builder.append_sql("NOT");
-    build_query_branch(builder, &not_expr.operand, explicit_only, ignore_case);
+    build_query_branch(
+        builder,
+        &not_expr.operand,
+        explicit_only,
+        ignore_case,
+        include_hierarchy,
+        use_closure,
+    );
 }
 
 fn build_and_query_branch(
@@ -178,10 +303,26 @@ fn build_and_query_branch(
     and_expr: &AndExpression,
     explicit_only: bool,
     ignore_case: bool,
+    include_hierarchy: bool,
+    use_closure: bool,
 ) {
-    build_query_branch(builder, &and_expr.left, explicit_only, ignore_case);
+    build_query_branch(
+        builder,
+        &and_expr.left,
+        explicit_only,
+        ignore_case,
+        include_hierarchy,
+        use_closure,
+    );
     builder.append_sql("AND");
-    build_query_branch(builder, &and_expr.right, explicit_only, ignore_case);
+    build_query_branch(
+        builder,
+        &and_expr.right,
+        explicit_only,
+        ignore_case,
+        include_hierarchy,
+        use_closure,
+    );
 }
 
 fn build_or_query_branch(
@@ -189,11 +330,27 @@ fn build_or_query_branch(
     or_expr: &OrExpression,
     explicit_only: bool,
     ignore_case: bool,
+    include_hierarchy: bool,
+    use_closure: bool,
 ) {
     builder.append_sql("(");
-    build_query_branch(builder, &or_expr.left, explicit_only, ignore_case);
+    build_query_branch(
+        builder,
+        &or_expr.left,
+        explicit_only,
+        ignore_case,
+        include_hierarchy,
+        use_closure,
+    );
     builder.append_sql("OR");
-    build_query_branch(builder, &or_expr.right, explicit_only, ignore_case);
+    build_query_branch(
+        builder,
+        &or_expr.right,
+        explicit_only,
+        ignore_case,
+        include_hierarchy,
+        use_closure,
+    );
     builder.append_sql(")");
 }
 
@@ -202,9 +359,43 @@ fn build_tag_query_branch(
     tag_expr: &TagExpression,
     explicit_only: bool,
     ignore_case: bool,
+    include_hierarchy: bool,
+    use_closure: bool,
 ) {
     let collation = collation_for(ignore_case);
 
+    if include_hierarchy && !explicit_only {
+        // Match the named tag or any of its transitive descendants in the containment hierarchy.
+        // The recursive CTE walks `child_tag_id` downwards from the parent, mirroring the shape of
+        // the implication traversal below but over `tag_hierarchy`.
+        builder.append_sql(
+            "
+id IN (SELECT file_id
+       FROM file_tag
+       WHERE tag_id IN (WITH RECURSIVE descendants (tag_id) AS
+                        (
+                            SELECT id
+                            FROM tag
+                            WHERE name",
+        );
+        builder.append_sql(collation);
+        builder.append_sql(" = ");
+        builder.append_param(tag_expr.tag.clone());
+        builder.append_sql(
+            "
+                            UNION ALL
+                            SELECT h.child_tag_id
+                            FROM tag_hierarchy h, descendants
+                            WHERE h.parent_tag_id = descendants.tag_id
+                        )
+                        SELECT tag_id
+                        FROM descendants
+                       )
+      )",
+        );
+        return;
+    }
+
     if explicit_only {
         builder.append_sql(
             "
@@ -222,6 +413,39 @@ id IN (SELECT file_id
                       )
       )",
         );
+    } else if use_closure {
+        // The implying pairs are precomputed in `implication_closure`, so the named tag and
+        // everything that transitively implies it can be gathered with a plain union instead of a
+        // recursive walk. The seed carries the wildcard value 0, matching every recorded value.
+        builder.append_sql(
+            "
+id IN (SELECT file_id
+       FROM file_tag
+       INNER JOIN (SELECT id AS tag_id, 0 AS value_id
+                   FROM tag
+                   WHERE name",
+        );
+        builder.append_sql(collation);
+        builder.append_sql(" = ");
+        builder.append_param(tag_expr.tag.clone());
+        builder.append_sql(
+            "
+                   UNION
+                   SELECT implication_closure.tag_id, implication_closure.value_id
+                   FROM implication_closure
+                   INNER JOIN tag ON tag.id = implication_closure.implied_tag_id
+                   WHERE tag.name",
+        );
+        builder.append_sql(collation);
+        builder.append_sql(" = ");
+        builder.append_param(tag_expr.tag.clone());
+        builder.append_sql(
+            "
+                  ) imps
+       ON file_tag.tag_id = imps.tag_id
+       AND (file_tag.value_id = imps.value_id OR imps.value_id = 0)
+      )",
+        );
     } else {
         builder.append_sql(
             "
@@ -254,17 +478,109 @@ id IN (SELECT file_id
     }
 }
 
+/// The type a comparison literal coerces to, used to decide whether the ordered operators
+/// (`<`, `<=`, `>`, `>=`) compare by magnitude rather than lexicographically. `=`/`!=` never use
+/// this: they always compare the raw stored string, so the same literal keeps feeding
+/// `Expression::exact_value_names` unchanged.
+#[derive(Debug, PartialEq)]
+enum ComparableKind {
+    Integer,
+    Float,
+    IsoDate,
+    Text,
+}
+
+fn classify_comparable(value: &str) -> ComparableKind {
+    if value.parse::<i64>().is_ok() {
+        ComparableKind::Integer
+    } else if value.parse::<f64>().is_ok() {
+        ComparableKind::Float
+    } else if is_iso_date(value) {
+        ComparableKind::IsoDate
+    } else {
+        ComparableKind::Text
+    }
+}
+
+/// A coarse `YYYY-MM-DD` shape check; actual validation (and normalization of e.g. `2021-1-1`) is
+/// left to SQLite's own `date()` function, which returns NULL for anything it can't parse.
+fn is_iso_date(value: &str) -> bool {
+    let mut parts = value.splitn(3, '-');
+    matches!(
+        (parts.next(), parts.next(), parts.next(), parts.next()),
+        (Some(y), Some(m), Some(d), None)
+            if !y.is_empty() && !m.is_empty() && !d.is_empty()
+                && y.chars().all(|c| c.is_ascii_digit())
+                && m.chars().all(|c| c.is_ascii_digit())
+                && d.chars().all(|c| c.is_ascii_digit())
+    )
+}
+
+/// Append `v.name`, cast/wrapped to match `kind` so it can be compared by magnitude to a
+/// similarly-wrapped literal.
+fn append_comparable_column(builder: &mut SqlBuilder, kind: &ComparableKind) {
+    builder.append_sql(match kind {
+        ComparableKind::Integer => "CAST(v.name AS integer)",
+        ComparableKind::Float => "CAST(v.name AS float)",
+        ComparableKind::IsoDate => "date(v.name)",
+        ComparableKind::Text => "v.name",
+    });
+}
+
+/// Guard against SQLite's `CAST` silently coercing non-numeric text to 0 (or the value of a
+/// leading-digit prefix) rather than failing: for the ordered operators' typed comparisons,
+/// require the stored value to round-trip unchanged through an integer or a real cast before it's
+/// compared by magnitude at all, so e.g. a tag value of "unknown" can never satisfy `size < 100`.
+/// Not needed for `IsoDate`, since `date()` already returns NULL (never true in a `WHERE` clause)
+/// for anything it can't parse; not needed for `Text`, which compares the raw stored string as it
+/// is.
+fn append_comparable_guard(builder: &mut SqlBuilder, kind: &ComparableKind) {
+    if matches!(kind, ComparableKind::Integer | ComparableKind::Float) {
+        builder.append_sql(
+            "(v.name = CAST(CAST(v.name AS integer) AS text)
+              OR v.name = CAST(CAST(v.name AS real) AS text)) AND ",
+        );
+    }
+}
+
+/// Append the comparison literal, wrapped the same way as `append_comparable_column` so both
+/// sides of the operator agree on how to interpret it.
+fn append_comparable_param(builder: &mut SqlBuilder, kind: &ComparableKind, value: String) {
+    match kind {
+        ComparableKind::IsoDate => {
+            builder.append_sql("date(");
+            builder.append_param(value);
+            builder.append_sql(")");
+        }
+        ComparableKind::Integer | ComparableKind::Float | ComparableKind::Text => {
+            builder.append_param(value);
+        }
+    }
+}
+
 fn build_comp_query_branch(
     builder: &mut SqlBuilder,
     comp_expr: &ComparisonExpression,
     explicit_only: bool,
     ignore_case: bool,
+    use_closure: bool,
 ) {
     let collation = collation_for(ignore_case);
 
-    let value_term = match comp_expr.value.parse::<f64>() {
-        Ok(_) => "CAST(v.name AS float)",
-        Err(_) => "v.name",
+    let is_ordered_operator = matches!(
+        comp_expr.operator,
+        Operator::LessThan
+            | Operator::LessThanOrEqual
+            | Operator::MoreThan
+            | Operator::MoreThanOrEqual
+    );
+
+    // `=`/`!=` always compare verbatim; only the ordered operators get typed comparisons, and
+    // only once the literal actually coerces to a number or an ISO date.
+    let comparable_kind = if is_ordered_operator {
+        classify_comparable(&comp_expr.value)
+    } else {
+        ComparableKind::Text
     };
 
     let mut operator = match comp_expr.operator {
@@ -310,6 +626,57 @@ id IN (SELECT file_id
             "           )
       )",
         );
+    } else if use_closure {
+        // The matching `tag=value` pairs and everything that transitively implies them are read
+        // straight out of `implication_closure`, joining `value` on the implied endpoint so the
+        // comparison is applied once and wildcard (value 0) closure rows are filtered out exactly
+        // as the recursive walk did.
+        builder.append_sql(
+            "
+id IN (SELECT file_id
+       FROM file_tag
+       INNER JOIN (SELECT t.id AS tag_id, v.id AS value_id
+                   FROM tag t, value v
+                   WHERE t.name",
+        );
+        builder.append_sql(collation);
+        builder.append_sql(" = ");
+        builder.append_param(comp_expr.tag.clone());
+        builder.append_sql(" AND ");
+        append_comparable_guard(builder, &comparable_kind);
+        append_comparable_column(builder, &comparable_kind);
+        builder.append_sql(collation);
+        builder.append_sql(" ");
+        builder.append_sql(operator);
+        builder.append_sql(" ");
+        append_comparable_param(builder, &comparable_kind, comp_expr.value.clone());
+        builder.append_sql(
+            "
+                   UNION
+                   SELECT implication_closure.tag_id, implication_closure.value_id
+                   FROM implication_closure
+                   INNER JOIN tag t ON t.id = implication_closure.implied_tag_id
+                   INNER JOIN value v ON v.id = implication_closure.implied_value_id
+                   WHERE t.name",
+        );
+        builder.append_sql(collation);
+        builder.append_sql(" = ");
+        builder.append_param(comp_expr.tag.clone());
+        builder.append_sql(" AND ");
+        append_comparable_guard(builder, &comparable_kind);
+        append_comparable_column(builder, &comparable_kind);
+        builder.append_sql(collation);
+        builder.append_sql(" ");
+        builder.append_sql(operator);
+        builder.append_sql(" ");
+        append_comparable_param(builder, &comparable_kind, comp_expr.value.clone());
+        builder.append_sql(
+            "
+                  ) impft
+       ON file_tag.tag_id = impft.tag_id AND
+          file_tag.value_id = impft.value_id
+      )",
+        );
     } else {
         builder.append_sql(
             "
@@ -323,12 +690,13 @@ id IN (WITH RECURSIVE impft (tag_id, value_id) AS
         builder.append_sql(" = ");
         builder.append_param(comp_expr.tag.clone());
         builder.append_sql(" AND ");
-        builder.append_sql(value_term);
+        append_comparable_guard(builder, &comparable_kind);
+        append_comparable_column(builder, &comparable_kind);
         builder.append_sql(collation);
         builder.append_sql(" ");
         builder.append_sql(operator);
         builder.append_sql(" ");
-        builder.append_param(comp_expr.value.clone());
+        append_comparable_param(builder, &comparable_kind, comp_expr.value.clone());
         builder.append_sql(
             "
            UNION ALL
@@ -389,3 +757,50 @@ fn build_sort(builder: &mut SqlBuilder, sort_type: FileSort) {
         FileSort::Size => builder.append_sql("ORDER BY size, directory || '/' || name"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_comparable_distinguishes_numeric_from_text() {
+        assert_eq!(classify_comparable("100"), ComparableKind::Integer);
+        assert_eq!(classify_comparable("3.14"), ComparableKind::Float);
+        assert_eq!(classify_comparable("2021-06-05"), ComparableKind::IsoDate);
+        // A mixed numeric/non-numeric value such as "unknown" must not be classified as
+        // comparable by magnitude, or it would be free to slip past the guard below.
+        assert_eq!(classify_comparable("unknown"), ComparableKind::Text);
+    }
+
+    #[test]
+    fn comparable_guard_excludes_non_numeric_values_from_ordered_comparisons() {
+        // A tag value of "unknown" stored alongside numeric values like "50" and "150" must never
+        // satisfy an ordered comparison such as `size < 100`: SQLite's `CAST("unknown" AS integer)`
+        // silently yields 0, which would otherwise make "unknown" < 100 true.
+        let mut builder = SqlBuilder::new();
+        append_comparable_guard(&mut builder, &ComparableKind::Integer);
+        append_comparable_column(&mut builder, &ComparableKind::Integer);
+        let sql = builder.sql();
+        assert!(
+            sql.contains("CAST(CAST(v.name AS integer) AS text)"),
+            "guard should require the stored value to round-trip through an integer cast, got: {}",
+            sql
+        );
+        assert!(
+            sql.contains("CAST(CAST(v.name AS real) AS text)"),
+            "guard should also accept values that round-trip through a real cast, got: {}",
+            sql
+        );
+    }
+
+    #[test]
+    fn comparable_guard_is_a_noop_for_text_and_iso_date() {
+        // `=`/`!=` always compare raw text, and `date()` already returns NULL for anything that
+        // doesn't parse, so neither kind needs the round-trip guard.
+        for kind in [ComparableKind::Text, ComparableKind::IsoDate] {
+            let mut builder = SqlBuilder::new();
+            append_comparable_guard(&mut builder, &kind);
+            assert_eq!(builder.sql(), "");
+        }
+    }
+}
@@ -46,6 +46,10 @@ fn parse_file_tag(row: Row) -> Result<FileTag> {
     })
 }
 
+// A batched counterpart to this function (one prepared statement reused across many inserts) and
+// an iterative directory walker to drive it were added and then fully reverted in this same
+// series, since there is no recursive-tagging command in this tree to call either of them.
+// Revisiting that needs a command to actually use it, not just the storage-layer plumbing.
 pub fn add_file_tag(
     tx: &mut Transaction,
     file_id: &FileId,
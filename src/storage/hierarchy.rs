@@ -0,0 +1,66 @@
+use crate::entities::{Tag, TagHierarchy, TagId};
+use crate::errors::*;
+use crate::storage::{Row, Transaction};
+
+pub fn hierarchies(tx: &mut Transaction) -> Result<Vec<TagHierarchy>> {
+    let sql = "
+SELECT parent.id, parent.name,
+       child.id, child.name
+FROM tag_hierarchy
+INNER JOIN tag parent ON tag_hierarchy.parent_tag_id = parent.id
+INNER JOIN tag child ON tag_hierarchy.child_tag_id = child.id
+ORDER BY parent.name, child.name";
+
+    tx.query_vec(sql, parse_hierarchy)
+}
+
+fn parse_hierarchy(row: Row) -> Result<TagHierarchy> {
+    Ok(TagHierarchy {
+        parent: Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+        },
+        child: Tag {
+            id: row.get(2)?,
+            name: row.get(3)?,
+        },
+    })
+}
+
+pub fn add_hierarchy(tx: &mut Transaction, parent_id: &TagId, child_id: &TagId) -> Result<usize> {
+    let sql = "
+INSERT OR IGNORE INTO tag_hierarchy (parent_tag_id, child_tag_id)
+VALUES (?1, ?2)";
+
+    let params = rusqlite::params![parent_id, child_id];
+    tx.execute_params(sql, params)
+}
+
+pub fn delete_hierarchy(tx: &mut Transaction, parent_id: &TagId, child_id: &TagId) -> Result<()> {
+    let sql = "
+DELETE FROM tag_hierarchy
+WHERE parent_tag_id = ?1 AND child_tag_id = ?2";
+
+    let params = rusqlite::params![parent_id, child_id];
+    match tx.execute_params(sql, params) {
+        Ok(0) => Err(format!(
+            "no such containment where tag {:?} contains tag {:?}",
+            parent_id, child_id
+        )
+        .into()),
+        Ok(1) => Ok(()),
+        Ok(_) => Err("expected exactly one row to be affected".into()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Remove every containment edge mentioning `tag_id`, so deleting a tag does not leave dangling
+/// parent or child references behind.
+pub fn delete_hierarchies_by_tag_id(tx: &mut Transaction, tag_id: &TagId) -> Result<usize> {
+    let sql = "
+DELETE FROM tag_hierarchy
+WHERE parent_tag_id = ?1 OR child_tag_id = ?1";
+
+    let params = rusqlite::params![tag_id];
+    tx.execute_params(sql, params)
+}
@@ -0,0 +1,79 @@
+use chrono::Utc;
+
+use crate::errors::*;
+use crate::storage::{Row, Transaction};
+
+/// Matches `storage::file`'s own private constant of the same name: kept local since it isn't
+/// visible across sibling modules.
+const TIMESTAMP_FORMAT: &str = "%F %T%.f%:z";
+
+fn ensure_history_table(tx: &mut Transaction) -> Result<()> {
+    let sql = "
+CREATE TABLE IF NOT EXISTS history (
+    id INTEGER PRIMARY KEY,
+    performed_at TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    changeset BLOB NOT NULL
+)";
+
+    tx.execute(sql)?;
+    Ok(())
+}
+
+/// A changeset recorded on the undo stack, along with the short description of the write it
+/// covers (e.g. "tag", "untag", "rename tag"). The time it was captured is kept in the `history`
+/// table's `performed_at` column but not loaded here.
+pub struct HistoryEntry {
+    pub id: u32,
+    pub operation: String,
+    pub changeset: Vec<u8>,
+}
+
+/// Push `changeset` (the bytes of a SQLite session changeset) onto the undo stack, tagged with a
+/// short human-readable `operation` description.
+pub fn record_changeset(tx: &mut Transaction, operation: &str, changeset: &[u8]) -> Result<()> {
+    ensure_history_table(tx)?;
+
+    let sql = "
+INSERT INTO history (performed_at, operation, changeset)
+VALUES (?, ?, ?)";
+
+    let performed_at = Utc::now().format(TIMESTAMP_FORMAT).to_string();
+    let params = rusqlite::params![performed_at, operation, changeset];
+    tx.execute_params(sql, params)?;
+    Ok(())
+}
+
+/// The most recently recorded entry still on the undo stack, if any.
+pub fn most_recent_changeset(tx: &mut Transaction) -> Result<Option<HistoryEntry>> {
+    ensure_history_table(tx)?;
+
+    let sql = "
+SELECT id, operation, changeset
+FROM history
+ORDER BY id DESC
+LIMIT 1";
+
+    tx.query_single(sql, parse_history_entry)
+}
+
+fn parse_history_entry(row: Row) -> Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        operation: row.get(1)?,
+        changeset: row.get(2)?,
+    })
+}
+
+/// Remove `id` from the undo stack, once it has been undone.
+pub fn delete_history_entry(tx: &mut Transaction, id: u32) -> Result<()> {
+    ensure_history_table(tx)?;
+
+    let sql = "
+DELETE FROM history
+WHERE id = ?";
+
+    let params = rusqlite::params![id];
+    tx.execute_params(sql, params)?;
+    Ok(())
+}
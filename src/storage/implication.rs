@@ -1,4 +1,9 @@
-use crate::entities::{Implication, OptionalValueId, Tag, TagId, TagIdValueIdPair, Value, ValueId};
+use std::collections::HashMap;
+
+use crate::entities::{
+    CompoundImplication, CompoundImplicationNamed, Implication, ImplicationRuleId, OptionalValueId,
+    Tag, TagId, TagIdValueIdPair, Value, ValueId,
+};
 use crate::errors::*;
 use crate::storage::{Row, SqlBuilder, Transaction};
 
@@ -154,3 +159,294 @@ WHERE value_id = ?1 OR implied_value_id = ?1";
     let params = rusqlite::params![value_id];
     tx.execute_params(sql, params)
 }
+
+/// Delete every compound rule that mentions `tag_id`, whether as one of its antecedents or as its
+/// consequent, so a deleted tag can't be referenced by a dangling rule. Returns the number of rules
+/// removed.
+pub fn delete_compound_implications_by_tag_id(tx: &mut Transaction, tag_id: &TagId) -> Result<usize> {
+    ensure_compound_tables(tx)?;
+
+    let sql = "
+SELECT DISTINCT rule_id
+FROM implication_rule_antecedent
+WHERE tag_id = ?1
+UNION
+SELECT id
+FROM implication_rule
+WHERE implied_tag_id = ?1";
+    let rule_ids: Vec<ImplicationRuleId> =
+        tx.query_vec_params(sql, rusqlite::params![tag_id], |row| row.get(0))?;
+
+    for rule_id in &rule_ids {
+        delete_compound_implication(tx, rule_id)?;
+    }
+
+    Ok(rule_ids.len())
+}
+
+/// Like `delete_compound_implications_by_tag_id`, but for a value.
+pub fn delete_compound_implications_by_value_id(
+    tx: &mut Transaction,
+    value_id: &ValueId,
+) -> Result<usize> {
+    ensure_compound_tables(tx)?;
+
+    let sql = "
+SELECT DISTINCT rule_id
+FROM implication_rule_antecedent
+WHERE value_id = ?1
+UNION
+SELECT id
+FROM implication_rule
+WHERE implied_value_id = ?1";
+    let rule_ids: Vec<ImplicationRuleId> =
+        tx.query_vec_params(sql, rusqlite::params![value_id], |row| row.get(0))?;
+
+    for rule_id in &rule_ids {
+        delete_compound_implication(tx, rule_id)?;
+    }
+
+    Ok(rule_ids.len())
+}
+
+/// Report whether the materialized `implication_closure` table is present. Databases created before
+/// the closure was introduced lack it, in which case the query builder falls back to walking the
+/// `implication` graph with a recursive CTE.
+pub fn closure_table_exists(tx: &mut Transaction) -> Result<bool> {
+    let sql = "
+SELECT 1
+FROM sqlite_master
+WHERE type = 'table' AND name = 'implication_closure'";
+
+    Ok(tx.query_single(sql, |row| row.get::<_, i64>(0))?.is_some())
+}
+
+/// Recompute the materialized transitive closure of the implication graph from scratch, creating
+/// the backing table if it does not yet exist.
+///
+/// Each row `(tag_id, value_id, implied_tag_id, implied_value_id)` records that a file tagged
+/// `tag_id[=value_id]` transitively implies `implied_tag_id[=implied_value_id]`. The closure is the
+/// fixpoint of `implication`: starting from the direct edges, an implication `b` is prepended to a
+/// closure row `c` whenever `b`'s implied endpoint matches `c`'s implying endpoint, using the same
+/// `value_id = 0` wildcard matching the recursive query relied on. The implication graph is kept
+/// acyclic (see `api::imply`), so the fixpoint always terminates.
+pub fn rebuild_closure(tx: &mut Transaction) -> Result<()> {
+    tx.execute(
+        "
+CREATE TABLE IF NOT EXISTS implication_closure (
+    tag_id INTEGER NOT NULL,
+    value_id INTEGER NOT NULL,
+    implied_tag_id INTEGER NOT NULL,
+    implied_value_id INTEGER NOT NULL,
+    PRIMARY KEY (tag_id, value_id, implied_tag_id, implied_value_id)
+)",
+    )?;
+    tx.execute("DELETE FROM implication_closure")?;
+
+    tx.execute(
+        "
+INSERT OR IGNORE INTO implication_closure (tag_id, value_id, implied_tag_id, implied_value_id)
+SELECT tag_id, value_id, implied_tag_id, implied_value_id
+FROM implication",
+    )?;
+
+    let extend_sql = "
+INSERT OR IGNORE INTO implication_closure (tag_id, value_id, implied_tag_id, implied_value_id)
+SELECT b.tag_id, b.value_id, c.implied_tag_id, c.implied_value_id
+FROM implication b, implication_closure c
+WHERE b.implied_tag_id = c.tag_id AND
+      (b.implied_value_id = c.value_id OR c.value_id = 0)";
+    while tx.execute(extend_sql)? > 0 {}
+
+    Ok(())
+}
+
+/// Create the tables backing conjunctive (multi-antecedent) implication rules, if they don't
+/// already exist. Kept separate from the single-antecedent `implication` table (rather than adding
+/// a `rule_id` column to it) so the existing single-antecedent storage, and the `implication_closure`
+/// query-time expansion built on it, are unaffected.
+fn ensure_compound_tables(tx: &mut Transaction) -> Result<()> {
+    tx.execute(
+        "
+CREATE TABLE IF NOT EXISTS implication_rule (
+    id INTEGER PRIMARY KEY,
+    implied_tag_id INTEGER NOT NULL,
+    implied_value_id INTEGER NOT NULL
+)",
+    )?;
+    tx.execute(
+        "
+CREATE TABLE IF NOT EXISTS implication_rule_antecedent (
+    rule_id INTEGER NOT NULL,
+    tag_id INTEGER NOT NULL,
+    value_id INTEGER NOT NULL,
+    PRIMARY KEY (rule_id, tag_id, value_id)
+)",
+    )?;
+
+    Ok(())
+}
+
+/// Add a rule whose consequent (`implied`) only applies when a file carries every pair in
+/// `antecedents`. A single-pair `antecedents` is accepted (equivalent to `add_implication`) so
+/// callers don't need to special-case the common case, but `add_implication` remains the preferred
+/// way to add one, since it keeps `implication_closure` usable for that rule.
+pub fn add_compound_implication(
+    tx: &mut Transaction,
+    antecedents: &[TagIdValueIdPair],
+    implied: &TagIdValueIdPair,
+) -> Result<ImplicationRuleId> {
+    error_chain::ensure!(
+        !antecedents.is_empty(),
+        "Bug: a compound implication needs at least one antecedent"
+    );
+
+    ensure_compound_tables(tx)?;
+
+    tx.execute_params(
+        "
+INSERT INTO implication_rule (implied_tag_id, implied_value_id)
+VALUES (?1, ?2)",
+        rusqlite::params![implied.tag_id, implied.value_id],
+    )?;
+    let rule_id = ImplicationRuleId(tx.last_inserted_row_id());
+
+    for antecedent in antecedents {
+        tx.execute_params(
+            "
+INSERT INTO implication_rule_antecedent (rule_id, tag_id, value_id)
+VALUES (?1, ?2, ?3)",
+            rusqlite::params![rule_id, antecedent.tag_id, antecedent.value_id],
+        )?;
+    }
+
+    Ok(rule_id)
+}
+
+pub fn delete_compound_implication(tx: &mut Transaction, rule_id: &ImplicationRuleId) -> Result<()> {
+    ensure_compound_tables(tx)?;
+
+    tx.execute_params(
+        "DELETE FROM implication_rule_antecedent WHERE rule_id = ?1",
+        rusqlite::params![rule_id],
+    )?;
+    let deleted = tx.execute_params(
+        "DELETE FROM implication_rule WHERE id = ?1",
+        rusqlite::params![rule_id],
+    )?;
+
+    if deleted == 0 {
+        return Err(format!("no such compound implication rule '{}'", rule_id).into());
+    }
+
+    Ok(())
+}
+
+/// Load every conjunctive implication rule, each paired with its full antecedent set.
+pub fn compound_implications(tx: &mut Transaction) -> Result<Vec<CompoundImplication>> {
+    ensure_compound_tables(tx)?;
+
+    let rules = tx.query_vec(
+        "
+SELECT id, implied_tag_id, implied_value_id
+FROM implication_rule",
+        |row| {
+            Ok((
+                row.get::<_, ImplicationRuleId>(0)?,
+                TagIdValueIdPair {
+                    tag_id: row.get(1)?,
+                    value_id: OptionalValueId::from_id(row.get(2)?),
+                },
+            ))
+        },
+    )?;
+
+    let antecedent_rows = tx.query_vec(
+        "
+SELECT rule_id, tag_id, value_id
+FROM implication_rule_antecedent",
+        |row| {
+            Ok((
+                row.get::<_, ImplicationRuleId>(0)?,
+                TagIdValueIdPair {
+                    tag_id: row.get(1)?,
+                    value_id: OptionalValueId::from_id(row.get(2)?),
+                },
+            ))
+        },
+    )?;
+
+    let mut antecedents_by_rule: HashMap<ImplicationRuleId, Vec<TagIdValueIdPair>> =
+        HashMap::new();
+    for (rule_id, antecedent) in antecedent_rows {
+        antecedents_by_rule
+            .entry(rule_id)
+            .or_default()
+            .push(antecedent);
+    }
+
+    Ok(rules
+        .into_iter()
+        .map(|(id, implied)| CompoundImplication {
+            antecedents: antecedents_by_rule.remove(&id).unwrap_or_default(),
+            id,
+            implied,
+        })
+        .collect())
+}
+
+/// Like `compound_implications`, but with tag/value names resolved, for display and for the
+/// cycle-detection graph in `api::imply`.
+pub fn compound_implications_named(tx: &mut Transaction) -> Result<Vec<CompoundImplicationNamed>> {
+    ensure_compound_tables(tx)?;
+
+    let rules = tx.query_vec(
+        "
+SELECT implication_rule.id, tag.id, tag.name, implication_rule.implied_value_id, value.name
+FROM implication_rule
+INNER JOIN tag ON tag.id = implication_rule.implied_tag_id
+LEFT OUTER JOIN value ON value.id = implication_rule.implied_value_id",
+        |row| {
+            let tag = Tag {
+                id: row.get(1)?,
+                name: row.get(2)?,
+            };
+            Ok((row.get::<_, ImplicationRuleId>(0)?, (tag, parse_opt_value(&row, 3, 4)?)))
+        },
+    )?;
+
+    let antecedent_rows = tx.query_vec(
+        "
+SELECT implication_rule_antecedent.rule_id,
+       tag.id, tag.name,
+       implication_rule_antecedent.value_id, value.name
+FROM implication_rule_antecedent
+INNER JOIN tag ON tag.id = implication_rule_antecedent.tag_id
+LEFT OUTER JOIN value ON value.id = implication_rule_antecedent.value_id",
+        |row| {
+            let tag = Tag {
+                id: row.get(1)?,
+                name: row.get(2)?,
+            };
+            Ok((row.get::<_, ImplicationRuleId>(0)?, (tag, parse_opt_value(&row, 3, 4)?)))
+        },
+    )?;
+
+    let mut antecedents_by_rule: HashMap<ImplicationRuleId, Vec<(Tag, Option<Value>)>> =
+        HashMap::new();
+    for (rule_id, antecedent) in antecedent_rows {
+        antecedents_by_rule
+            .entry(rule_id)
+            .or_default()
+            .push(antecedent);
+    }
+
+    Ok(rules
+        .into_iter()
+        .map(|(id, implied)| CompoundImplicationNamed {
+            antecedents: antecedents_by_rule.remove(&id).unwrap_or_default(),
+            id,
+            implied,
+        })
+        .collect())
+}
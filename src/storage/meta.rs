@@ -4,19 +4,23 @@
 //! responsibilities too much.
 //!
 //! Note that it might be cleaner to move this eventually to the "api" layer, e.g. in a "common" submodule.
-use crate::entities::{FileId, FileTag, OptionalValueId, Tag, TagId, Value};
+use std::collections::HashMap;
+
+use crate::entities::{FileId, FileTag, OptionalValueId, Tag, TagId, TagIdValueIdPair, Value};
 use crate::errors::*;
 use crate::storage::{self, Transaction};
 
 pub fn delete_tag(tx: &mut Transaction, tag: &Tag) -> Result<()> {
     delete_file_tags_by_tag_id(tx, tag)?;
     storage::implication::delete_implications_by_tag_id(tx, &tag.id)?;
+    storage::implication::delete_compound_implications_by_tag_id(tx, &tag.id)?;
     storage::tag::delete_tag(tx, &tag.id)
 }
 
 pub fn delete_value(tx: &mut Transaction, value: &Value) -> Result<()> {
     delete_file_tags_by_value_id(tx, value)?;
     storage::implication::delete_implications_by_value_id(tx, &value.id)?;
+    storage::implication::delete_compound_implications_by_value_id(tx, &value.id)?;
     storage::value::delete_value(tx, &value.id)
 }
 
@@ -97,48 +101,116 @@ pub fn add_implied_file_tags(
     tx: &mut Transaction,
     file_tags: Vec<FileTag>,
 ) -> Result<Vec<FileTag>> {
-    let mut all_file_tags = file_tags.clone();
+    let file_id = match file_tags.first() {
+        Some(file_tag) => file_tag.file_id,
+        None => return Ok(file_tags),
+    };
 
-    let mut to_process = file_tags;
-    while !to_process.is_empty() {
-        let file_tag = to_process.pop().unwrap();
+    let mut all_file_tags = file_tags.clone();
 
-        let implications =
-            storage::implication::implications_for(tx, &[file_tag.to_tag_id_value_id_pair()])?;
+    // Index every (tag_id, value_id) pair we currently know about by its position in
+    // `all_file_tags`, so both fixpoints below look a pair up in O(1) instead of rescanning the
+    // whole vector for every implication.
+    let mut index: HashMap<(TagId, OptionalValueId), usize> = all_file_tags
+        .iter()
+        .enumerate()
+        .map(|(i, ft)| ((ft.tag_id, ft.value_id), i))
+        .collect();
+
+    // Conjunctive rules only fire once *every* one of their (possibly several) antecedents is
+    // present, so they can't be driven by the single-pair lookup below on their own. Index each
+    // rule by its antecedent pairs so a round only re-examines the rules referencing a pair just
+    // derived, rather than rescanning every rule. The implication graph is kept acyclic (see
+    // `api::imply::ensure_no_cycles`), so the fixpoint below always terminates.
+    let rules = storage::implication::compound_implications(tx)?;
+    let mut rules_by_antecedent: HashMap<(TagId, OptionalValueId), Vec<usize>> = HashMap::new();
+    for (rule_idx, rule) in rules.iter().enumerate() {
+        for antecedent in &rule.antecedents {
+            rules_by_antecedent
+                .entry((antecedent.tag_id, antecedent.value_id))
+                .or_default()
+                .push(rule_idx);
+        }
+    }
 
-        for implication in implications.iter() {
-            let existing_file_tag_opt = find_file_tag_for_pair(
+    // Expand both single-antecedent implications and conjunctive rules from one shared worklist,
+    // with a semi-naive closure: `delta` holds only the pairs derived (or given) since the last
+    // round, so each pair is checked against both `implications_for` and `rules_by_antecedent`
+    // exactly once. Crucially this means a pair derived by a compound rule is fed back through
+    // `implications_for` just like any other pair (and vice versa), so chains mixing both kinds of
+    // implication (e.g. a compound rule deriving a tag that a simple rule implies further) are
+    // still fully resolved.
+    let mut delta: Vec<(TagId, OptionalValueId)> = all_file_tags
+        .iter()
+        .map(|ft| (ft.tag_id, ft.value_id))
+        .collect();
+    while let Some((tag_id, value_id)) = delta.pop() {
+        let pair = TagIdValueIdPair { tag_id, value_id };
+        let implications = storage::implication::implications_for(tx, &[pair])?;
+
+        for implication in &implications {
+            apply_implied_pair(
                 &mut all_file_tags,
-                &implication.implied_tag.id,
-                &implication.implied_value,
+                &mut index,
+                &mut delta,
+                file_id,
+                implication.implied_tag.id,
+                OptionalValueId::from_opt_value(&implication.implied_value),
             );
+        }
 
-            match existing_file_tag_opt {
-                Some(file_tag) => file_tag.implicit = true,
-                None => {
-                    let new_file_tag = FileTag {
-                        file_id: file_tag.file_id,
-                        tag_id: implication.implied_tag.id,
-                        value_id: OptionalValueId::from_opt_value(&implication.implied_value),
-                        explicit: false,
-                        implicit: true,
-                    };
-                    all_file_tags.push(new_file_tag.clone());
-                    to_process.push(new_file_tag);
+        if let Some(candidate_rules) = rules_by_antecedent.get(&(tag_id, value_id)) {
+            for &rule_idx in candidate_rules {
+                let rule = &rules[rule_idx];
+                let satisfied = rule
+                    .antecedents
+                    .iter()
+                    .all(|a| index.contains_key(&(a.tag_id, a.value_id)));
+                if !satisfied {
+                    continue;
                 }
-            };
+
+                apply_implied_pair(
+                    &mut all_file_tags,
+                    &mut index,
+                    &mut delta,
+                    file_id,
+                    rule.implied.tag_id,
+                    rule.implied.value_id,
+                );
+            }
         }
     }
 
     Ok(all_file_tags)
 }
 
-fn find_file_tag_for_pair<'a>(
-    file_tags: &'a mut Vec<FileTag>,
-    tag_id: &TagId,
-    opt_value: &Option<Value>,
-) -> Option<&'a mut FileTag> {
-    file_tags.iter_mut().find(|ft| {
-        ft.tag_id == *tag_id && ft.value_id == OptionalValueId::from_opt_value(opt_value)
-    })
+/// Ensure that `(tag_id, value_id)` is present in `file_tags` as an implicit tag for `file_id`.
+/// An already-known pair is flipped to implicit in O(1) via `index`; a new one is appended to
+/// `file_tags`, indexed, and queued onto `delta` so the fixpoint keeps expanding from it.
+fn apply_implied_pair(
+    file_tags: &mut Vec<FileTag>,
+    index: &mut HashMap<(TagId, OptionalValueId), usize>,
+    delta: &mut Vec<(TagId, OptionalValueId)>,
+    file_id: FileId,
+    tag_id: TagId,
+    value_id: OptionalValueId,
+) {
+    match index.get(&(tag_id, value_id)) {
+        Some(&i) => {
+            file_tags[i].implicit = true;
+        }
+        None => {
+            let new_file_tag = FileTag {
+                file_id,
+                tag_id,
+                value_id,
+                explicit: false,
+                implicit: true,
+            };
+            index.insert((tag_id, value_id), file_tags.len());
+            file_tags.push(new_file_tag);
+            delta.push((tag_id, value_id));
+        }
+    }
 }
@@ -0,0 +1,57 @@
+use crate::errors::*;
+use crate::storage::Transaction;
+
+/// The schema version this build of tmsu understands. Bump this and append a `Migration` whenever
+/// the schema changes in a way that existing databases need to be brought forward for.
+pub const CURRENT_VERSION: i64 = 1;
+
+/// One forward step in the schema's history. `version` is the `PRAGMA user_version` a database
+/// reaches once `run` has been applied. Migrations are kept in ascending `version` order and are
+/// append-only: once released, a migration is never edited, only superseded by a later one.
+struct Migration {
+    version: i64,
+    run: fn(&mut Transaction) -> Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    run: baseline,
+}];
+
+/// Version 1 is the baseline recorded the day `user_version` tracking was introduced. Every table
+/// is (and was already) created on demand by its owning `storage` submodule, e.g. `storage::tag`'s
+/// own `CREATE TABLE IF NOT EXISTS` — so there's no schema to build here. This step exists purely
+/// to give a pre-existing database (which predates version tracking, and therefore reads as 0) a
+/// version to land on.
+fn baseline(_tx: &mut Transaction) -> Result<()> {
+    Ok(())
+}
+
+/// Bring `tx`'s database up to `CURRENT_VERSION`, applying every migration beyond its current
+/// `user_version` in order and bumping `user_version` after each one. Run within the caller's
+/// transaction, so a failure partway through leaves the database at its original version.
+///
+/// Refuses (hard error, not a silent no-op) to proceed against a database newer than
+/// `CURRENT_VERSION`: there is no such thing as a downgrade migration, and letting an older binary
+/// write to a schema it doesn't understand risks silent corruption.
+pub fn migrate(tx: &mut Transaction) -> Result<()> {
+    let version = tx.user_version()?;
+
+    if version > CURRENT_VERSION {
+        return Err(format!(
+            "database schema version {} is newer than this version of tmsu supports (up to {}); \
+             upgrade tmsu to open it",
+            version, CURRENT_VERSION
+        )
+        .into());
+    }
+
+    for migration in MIGRATIONS {
+        if migration.version > version {
+            (migration.run)(tx)?;
+            tx.set_user_version(migration.version)?;
+        }
+    }
+
+    Ok(())
+}
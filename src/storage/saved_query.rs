@@ -0,0 +1,71 @@
+use crate::entities::SavedQuery;
+use crate::errors::*;
+use crate::storage::{Row, Transaction};
+
+/// Create the table backing saved queries, if it doesn't already exist. Kept self-contained (like
+/// `implication::ensure_compound_tables`): the table is created on first use rather than by a
+/// step in `storage::migrations`.
+fn ensure_saved_query_table(tx: &mut Transaction) -> Result<()> {
+    tx.execute(
+        "
+CREATE TABLE IF NOT EXISTS saved_query (
+    name TEXT PRIMARY KEY,
+    query TEXT NOT NULL
+)",
+    )?;
+
+    Ok(())
+}
+
+pub fn saved_queries(tx: &mut Transaction) -> Result<Vec<SavedQuery>> {
+    ensure_saved_query_table(tx)?;
+
+    let sql = "
+SELECT name, query
+FROM saved_query
+ORDER BY name";
+
+    tx.query_vec(sql, parse_saved_query)
+}
+
+pub fn saved_query_by_name(tx: &mut Transaction, name: &str) -> Result<Option<SavedQuery>> {
+    ensure_saved_query_table(tx)?;
+
+    let sql = "
+SELECT name, query
+FROM saved_query
+WHERE name = ?";
+
+    let params = rusqlite::params![name];
+    let results = tx.query_vec_params(sql, params, parse_saved_query)?;
+    Ok(results.into_iter().next())
+}
+
+pub fn update_saved_query(tx: &mut Transaction, name: &str, query: &str) -> Result<usize> {
+    ensure_saved_query_table(tx)?;
+
+    let sql = "
+INSERT OR REPLACE INTO saved_query (name, query)
+VALUES (?, ?)";
+
+    let params = rusqlite::params![name, query];
+    tx.execute_params(sql, params)
+}
+
+pub fn delete_saved_query(tx: &mut Transaction, name: &str) -> Result<usize> {
+    ensure_saved_query_table(tx)?;
+
+    let sql = "
+DELETE FROM saved_query
+WHERE name = ?";
+
+    let params = rusqlite::params![name];
+    tx.execute_params(sql, params)
+}
+
+fn parse_saved_query(row: Row) -> Result<SavedQuery> {
+    Ok(SavedQuery {
+        name: row.get(0)?,
+        query: row.get(1)?,
+    })
+}
@@ -13,9 +13,11 @@ FROM setting";
     let db_settings = tx.query_vec(sql, parse_setting)?;
     // Override the default settings with the ones from DB
     for (name, value) in db_settings {
-        // Explicitly ignore settings from the DB which are invalid.
-        // This differs from the Go implementation.
-        settings.set(&name, &value).ok();
+        // Warn about, but otherwise ignore, settings from the DB which are invalid (e.g. a typo
+        // stored before validation was enforced on write). This differs from the Go implementation.
+        if let Err(e) = settings.set(&name, &value) {
+            warn!("ignoring invalid stored setting '{}': {}", name, e);
+        }
     }
 
     Ok(settings)
@@ -30,6 +32,15 @@ VALUES (?, ?)";
     tx.execute_params(sql, params)
 }
 
+pub fn delete_setting(tx: &mut Transaction, name: &str) -> Result<usize> {
+    let sql = "
+DELETE FROM setting
+WHERE name = ?";
+
+    let params = rusqlite::params![name];
+    tx.execute_params(sql, params)
+}
+
 fn parse_setting(row: Row) -> Result<(String, String)> {
     Ok((row.get(0)?, row.get(1)?))
 }
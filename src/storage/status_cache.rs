@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use chrono::{DateTime, Utc};
+
+use crate::errors::*;
+use crate::storage::Transaction;
+
+const TIMESTAMP_FORMAT: &str = "%F %T%.f%:z";
+
+/// One entry found directly inside a cached directory.
+pub struct DirCacheEntry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// A directory's state as of the last `status` run that actually listed it: used to skip the
+/// `read_dir` call on a later run when the directory's own mtime hasn't moved. Whether each child
+/// is currently tagged is still re-derived fresh every run against the database, so a stale cache
+/// entry can never report the wrong status — it only saves the directory listing itself.
+pub struct CachedDirectory {
+    pub mtime: DateTime<Utc>,
+    pub entry_hash: String,
+    pub children: Vec<DirCacheEntry>,
+}
+
+fn ensure_status_cache_tables(tx: &mut Transaction) -> Result<()> {
+    tx.execute(
+        "
+CREATE TABLE IF NOT EXISTS status_dir_cache (
+    path TEXT PRIMARY KEY,
+    mtime TEXT NOT NULL,
+    entry_hash TEXT NOT NULL
+)",
+    )?;
+    tx.execute(
+        "
+CREATE TABLE IF NOT EXISTS status_dir_cache_entry (
+    dir_path TEXT NOT NULL,
+    name TEXT NOT NULL,
+    is_dir INTEGER NOT NULL,
+    PRIMARY KEY (dir_path, name)
+)",
+    )?;
+
+    Ok(())
+}
+
+/// Load the full cache in one pass, keyed by (absolute) directory path, so that the parallel
+/// status walk can consult it by reference instead of querying the database from inside a worker.
+pub fn all_cached_directories(tx: &mut Transaction) -> Result<HashMap<String, CachedDirectory>> {
+    ensure_status_cache_tables(tx)?;
+
+    let headers = tx.query_vec(
+        "SELECT path, mtime, entry_hash FROM status_dir_cache",
+        |row| {
+            let path: String = row.get(0)?;
+            let mtime_str: String = row.get(1)?;
+            let mtime = DateTime::parse_from_str(&mtime_str, TIMESTAMP_FORMAT)?.with_timezone(&Utc);
+            let entry_hash: String = row.get(2)?;
+            Ok((path, mtime, entry_hash))
+        },
+    )?;
+
+    let entry_rows = tx.query_vec(
+        "SELECT dir_path, name, is_dir FROM status_dir_cache_entry",
+        |row| {
+            let dir_path: String = row.get(0)?;
+            let name: String = row.get(1)?;
+            let is_dir: i64 = row.get(2)?;
+            Ok((dir_path, name, is_dir != 0))
+        },
+    )?;
+
+    let mut children_by_dir: HashMap<String, Vec<DirCacheEntry>> = HashMap::new();
+    for (dir_path, name, is_dir) in entry_rows {
+        children_by_dir
+            .entry(dir_path)
+            .or_default()
+            .push(DirCacheEntry { name, is_dir });
+    }
+
+    Ok(headers
+        .into_iter()
+        .map(|(path, mtime, entry_hash)| {
+            let children = children_by_dir.remove(&path).unwrap_or_default();
+            (
+                path,
+                CachedDirectory {
+                    mtime,
+                    entry_hash,
+                    children,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Replace the cached listing for each directory in `records` with its freshly observed state.
+pub fn record_directories(
+    tx: &mut Transaction,
+    records: &[(String, CachedDirectory)],
+) -> Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    ensure_status_cache_tables(tx)?;
+
+    for (path, cached) in records {
+        tx.execute_params(
+            "DELETE FROM status_dir_cache WHERE path = ?",
+            rusqlite::params![path],
+        )?;
+        tx.execute_params(
+            "DELETE FROM status_dir_cache_entry WHERE dir_path = ?",
+            rusqlite::params![path],
+        )?;
+
+        tx.execute_params(
+            "
+INSERT INTO status_dir_cache (path, mtime, entry_hash)
+VALUES (?, ?, ?)",
+            rusqlite::params![
+                path,
+                cached.mtime.format(TIMESTAMP_FORMAT).to_string(),
+                cached.entry_hash
+            ],
+        )?;
+
+        for child in &cached.children {
+            tx.execute_params(
+                "
+INSERT INTO status_dir_cache_entry (dir_path, name, is_dir)
+VALUES (?, ?, ?)",
+                rusqlite::params![path, child.name, child.is_dir as i64],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A stable hash of a directory's entry names, recorded alongside its mtime so a cached listing
+/// carries enough information to tell, after the fact, whether it was actually still current.
+pub fn hash_entry_names<'a>(names: impl Iterator<Item = &'a str>) -> String {
+    let mut sorted: Vec<&str> = names.collect();
+    sorted.sort_unstable();
+
+    let mut hasher = DefaultHasher::new();
+    for name in sorted {
+        name.hash(&mut hasher);
+        0u8.hash(&mut hasher); // separator, so ["ab", "c"] doesn't hash the same as ["a", "bc"]
+    }
+
+    format!("{:x}", hasher.finish())
+}
@@ -1,6 +1,6 @@
 use crate::entities::{Tag, TagFileCount, TagId};
 use crate::errors::*;
-use crate::storage::{self, collation_for, Row, Transaction};
+use crate::storage::{collation_for, Row, Transaction};
 
 pub fn tag_count(tx: &mut Transaction) -> Result<u64> {
     tx.count_from_table("tag")
@@ -26,22 +26,24 @@ WHERE id = ?";
 }
 
 pub fn tags_by_names(tx: &mut Transaction, names: &[&str], ignore_case: bool) -> Result<Vec<Tag>> {
-    if names.is_empty() {
-        return Ok(vec![]);
-    }
-
     let collation = collation_for(ignore_case);
-    let (placeholders, params) = storage::generate_placeholders(names)?;
 
-    let sql = format!(
-        "
+    // Chunked since `names` comes straight from the command line and could in principle exceed
+    // SQLite's bound-parameter limit.
+    tx.query_vec_chunked(
+        &[],
+        names,
+        |placeholders| {
+            format!(
+                "
 SELECT id, name
 FROM tag
 WHERE name {} IN ({})",
-        collation, &placeholders
-    );
-
-    tx.query_vec_params(&sql, &params, parse_tag)
+                collation, placeholders
+            )
+        },
+        parse_tag,
+    )
 }
 
 pub fn tag_by_name(tx: &mut Transaction, name: &str) -> Result<Option<Tag>> {
@@ -102,6 +104,25 @@ WHERE id = ?";
     }
 }
 
+/// Count the tags which are not referenced by any file-tag, i.e. those that can be reclaimed.
+pub fn unused_tag_count(tx: &mut Transaction) -> Result<u64> {
+    let sql = "
+SELECT count(1)
+FROM tag
+WHERE id NOT IN (SELECT DISTINCT tag_id FROM file_tag)";
+
+    Ok(tx.query_single(sql, |row| row.get::<_, u32>(0))?.unwrap_or(0) as u64)
+}
+
+/// Delete every tag which is not referenced by any file-tag, returning the number of rows removed.
+pub fn delete_unused_tags(tx: &mut Transaction) -> Result<u64> {
+    let sql = "
+DELETE FROM tag
+WHERE id NOT IN (SELECT DISTINCT tag_id FROM file_tag)";
+
+    Ok(tx.execute(sql)? as u64)
+}
+
 /// Retrieve the usage (file count) of each tag
 pub fn tag_usage(tx: &mut Transaction) -> Result<Vec<TagFileCount>> {
     let sql = "
@@ -1,6 +1,6 @@
 use crate::entities::{TagId, Value, ValueId};
 use crate::errors::*;
-use crate::storage::{self, Row, Transaction};
+use crate::storage::{Row, Transaction};
 
 pub fn value_count(tx: &mut Transaction) -> Result<u64> {
     tx.count_from_table("value")
@@ -16,21 +16,22 @@ ORDER BY name";
 }
 
 pub fn values_by_names(tx: &mut Transaction, names: &[&str]) -> Result<Vec<Value>> {
-    if names.is_empty() {
-        return Ok(vec![]);
-    }
-
-    let (placeholders, params) = storage::generate_placeholders(names)?;
-
-    let sql = format!(
-        "
+    // Chunked since `names` comes straight from the command line and could in principle exceed
+    // SQLite's bound-parameter limit.
+    tx.query_vec_chunked(
+        &[],
+        names,
+        |placeholders| {
+            format!(
+                "
 SELECT id, name
 FROM value
 WHERE name IN ({})",
-        &placeholders
-    );
-
-    tx.query_vec_params(&sql, &params, parse_value)
+                placeholders
+            )
+        },
+        parse_value,
+    )
 }
 
 pub fn value_by_name(tx: &mut Transaction, name: &str) -> Result<Option<Value>> {
@@ -56,6 +57,25 @@ ORDER BY name";
     tx.query_vec_params(sql, params, parse_value)
 }
 
+/// Count the values which are not referenced by any file-tag, i.e. those that can be reclaimed.
+pub fn unused_value_count(tx: &mut Transaction) -> Result<u64> {
+    let sql = "
+SELECT count(1)
+FROM value
+WHERE id NOT IN (SELECT DISTINCT value_id FROM file_tag)";
+
+    Ok(tx.query_single(sql, |row| row.get::<_, u32>(0))?.unwrap_or(0) as u64)
+}
+
+/// Delete every value which is not referenced by any file-tag, returning the number of rows removed.
+pub fn delete_unused_values(tx: &mut Transaction) -> Result<u64> {
+    let sql = "
+DELETE FROM value
+WHERE id NOT IN (SELECT DISTINCT value_id FROM file_tag)";
+
+    Ok(tx.execute(sql)? as u64)
+}
+
 fn parse_value(row: Row) -> Result<Value> {
     Ok(Value {
         id: row.get(0)?,